@@ -0,0 +1,330 @@
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::prelude::*;
+use bevy::reflect::TypePath;
+use bevy::sprite::Anchor;
+use bevy::utils::BoxedFuture;
+use serde::Deserialize;
+use std::collections::HashMap;
+use thiserror::Error;
+
+use crate::state::ScheduleSet;
+
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<AnimationGraph>()
+            .init_asset_loader::<AnimationGraphLoader>()
+            .init_resource::<AnimationSheets>()
+            .add_event::<AnimationEvent>()
+            .configure_sets(
+                Update,
+                AnimationSet.in_set(ScheduleSet::PostTransformUpdate),
+            )
+            .add_systems(
+                Update,
+                (update_animation_states, advance_animations)
+                    .chain()
+                    .in_set(AnimationSet),
+            );
+    }
+}
+
+/// Systems driving the `Animator`s. Systems writing `AnimationParams` should run
+/// before this set.
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct AnimationSet;
+
+/// Animation graph loaded from a `.anim.ron` file. It describes the clips of an
+/// entity and a state machine choosing between them.
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct AnimationGraph {
+    pub initial: String,
+    pub clips: HashMap<String, AnimationClip>,
+    pub states: HashMap<String, AnimationState>,
+}
+
+/// A range of frames of a sheet registered in `AnimationSheets`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct AnimationClip {
+    pub sheet: String,
+    pub first: usize,
+    pub last: usize,
+    pub fps: f32,
+    #[serde(default = "default_looping")]
+    pub looping: bool,
+    #[serde(default)]
+    pub events: Vec<FrameEvent>,
+}
+
+/// Marker emitted as an `AnimationEvent` whenever `frame` (counted from the
+/// first frame of the clip) is shown.
+#[derive(Deserialize, Debug, Clone)]
+pub struct FrameEvent {
+    pub frame: usize,
+    pub name: String,
+}
+
+/// Sent by the animation system for each `FrameEvent` of a clip reaching its frame.
+#[derive(Event, Debug, Clone)]
+pub struct AnimationEvent {
+    pub entity: Entity,
+    pub name: String,
+}
+
+fn default_looping() -> bool {
+    true
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct AnimationState {
+    pub clip: String,
+    /// Checked in order, the first transition whose conditions all hold is taken.
+    #[serde(default)]
+    pub transitions: Vec<AnimationTransition>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct AnimationTransition {
+    pub to: String,
+    #[serde(default)]
+    pub when: Vec<AnimationCondition>,
+}
+
+/// Conditions on the `AnimationParams` of an entity or on the current clip.
+#[derive(Deserialize, Debug, Clone)]
+pub enum AnimationCondition {
+    Greater(String, f32),
+    Less(String, f32),
+    IsTrue(String),
+    IsFalse(String),
+    /// The current clip is not looping and has shown its last frame.
+    Finished,
+    /// The current clip emitted the named event in the last frame.
+    Fired(String),
+}
+
+/// Texture atlas of an animation together with its pivot: the pixel of a frame,
+/// relative to the frame center, that is placed on the origin of the entity.
+#[derive(Clone, Debug)]
+pub struct AnimationSheet {
+    pub atlas: Handle<TextureAtlas>,
+    pub tile_size: Vec2,
+    pub pivot: Vec2,
+}
+
+impl AnimationSheet {
+    pub fn anchor(&self, flip_x: bool) -> Anchor {
+        let mut anchor = self.pivot / self.tile_size;
+        if flip_x {
+            anchor.x = -anchor.x;
+        }
+        Anchor::Custom(anchor)
+    }
+}
+
+/// Sheets referenced by name from the clips of animation graphs.
+#[derive(Resource, Default, Debug)]
+pub struct AnimationSheets(pub HashMap<String, AnimationSheet>);
+
+/// Values the transition conditions of an `Animator` are evaluated against.
+/// Booleans are stored as `1.0` and `0.0`.
+#[derive(Component, Default, Debug, Clone)]
+pub struct AnimationParams(HashMap<String, f32>);
+
+impl AnimationParams {
+    pub fn set_float(&mut self, name: &str, value: f32) {
+        match self.0.get_mut(name) {
+            Some(current) => *current = value,
+            None => {
+                self.0.insert(name.to_string(), value);
+            }
+        }
+    }
+
+    pub fn set_bool(&mut self, name: &str, value: bool) {
+        self.set_float(name, if value { 1.0 } else { 0.0 });
+    }
+
+    pub fn get(&self, name: &str) -> f32 {
+        self.0.get(name).copied().unwrap_or(0.0)
+    }
+}
+
+/// Plays an `AnimationGraph` on the `TextureAtlasSprite` of the same entity.
+#[derive(Component, Debug, Clone)]
+pub struct Animator {
+    pub graph: Handle<AnimationGraph>,
+    state: Option<String>,
+    frame: usize,
+    elapsed: f32,
+    finished: bool,
+    entered: bool,
+    fired: Vec<String>,
+}
+
+impl Animator {
+    pub fn new(graph: Handle<AnimationGraph>) -> Self {
+        Animator {
+            graph,
+            state: None,
+            frame: 0,
+            elapsed: 0.0,
+            finished: false,
+            entered: false,
+            fired: Vec::new(),
+        }
+    }
+
+    pub fn state(&self) -> Option<&str> {
+        self.state.as_deref()
+    }
+
+    fn enter(&mut self, state: String) {
+        self.state = Some(state);
+        self.frame = 0;
+        self.elapsed = 0.0;
+        self.finished = false;
+        self.entered = true;
+        self.fired.clear();
+    }
+}
+
+fn update_animation_states(
+    mut query: Query<(&mut Animator, &AnimationParams)>,
+    graphs: Res<Assets<AnimationGraph>>,
+) {
+    for (mut animator, params) in query.iter_mut() {
+        let Some(graph) = graphs.get(&animator.graph) else {
+            continue;
+        };
+        let Some(state_name) = animator.state.clone() else {
+            animator.enter(graph.initial.clone());
+            continue;
+        };
+        let Some(state) = graph.states.get(&state_name) else {
+            warn!("Animation state {} does not exist", state_name);
+            animator.enter(graph.initial.clone());
+            continue;
+        };
+
+        let next = state.transitions.iter().find(|transition| {
+            transition.when.iter().all(|condition| match condition {
+                AnimationCondition::Greater(name, value) => params.get(name) > *value,
+                AnimationCondition::Less(name, value) => params.get(name) < *value,
+                AnimationCondition::IsTrue(name) => params.get(name) != 0.0,
+                AnimationCondition::IsFalse(name) => params.get(name) == 0.0,
+                AnimationCondition::Finished => animator.finished,
+                AnimationCondition::Fired(name) => animator.fired.contains(name),
+            })
+        });
+        if let Some(transition) = next {
+            animator.enter(transition.to.clone());
+        }
+    }
+}
+
+fn advance_animations(
+    mut query: Query<(
+        Entity,
+        &mut Animator,
+        &mut TextureAtlasSprite,
+        &mut Handle<TextureAtlas>,
+    )>,
+    graphs: Res<Assets<AnimationGraph>>,
+    sheets: Res<AnimationSheets>,
+    time: Res<Time>,
+    mut animation_events: EventWriter<AnimationEvent>,
+) {
+    for (entity, mut animator, mut sprite, mut atlas) in query.iter_mut() {
+        let Some(graph) = graphs.get(&animator.graph) else {
+            continue;
+        };
+        let Some(clip) = animator
+            .state
+            .as_ref()
+            .and_then(|state| graph.states.get(state))
+            .and_then(|state| graph.clips.get(&state.clip))
+        else {
+            continue;
+        };
+
+        let mut shown_frames = Vec::new();
+        if animator.entered {
+            animator.entered = false;
+            shown_frames.push(0);
+        }
+
+        let frame_count = clip.last.saturating_sub(clip.first) + 1;
+        let frame_duration = 1.0 / clip.fps.max(0.001);
+        animator.elapsed += time.delta_seconds();
+        while animator.elapsed >= frame_duration && !animator.finished {
+            animator.elapsed -= frame_duration;
+            if animator.frame + 1 < frame_count {
+                animator.frame += 1;
+            } else if clip.looping {
+                animator.frame = 0;
+            } else {
+                animator.finished = true;
+                continue;
+            }
+            shown_frames.push(animator.frame);
+        }
+
+        animator.fired.clear();
+        for frame_event in clip
+            .events
+            .iter()
+            .filter(|frame_event| shown_frames.contains(&frame_event.frame))
+        {
+            animator.fired.push(frame_event.name.clone());
+            animation_events.send(AnimationEvent {
+                entity,
+                name: frame_event.name.clone(),
+            });
+        }
+
+        sprite.index = clip.first + animator.frame;
+        if let Some(sheet) = sheets.0.get(&clip.sheet) {
+            if *atlas != sheet.atlas {
+                *atlas = sheet.atlas.clone();
+            }
+            sprite.anchor = sheet.anchor(sprite.flip_x);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct AnimationGraphLoader;
+
+#[derive(Debug, Error)]
+pub enum AnimationGraphLoaderError {
+    #[error("Could not read animation graph: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse animation graph: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+impl AssetLoader for AnimationGraphLoader {
+    type Asset = AnimationGraph;
+    type Settings = ();
+    type Error = AnimationGraphLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<AnimationGraph, AnimationGraphLoaderError>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            Ok(ron::de::from_bytes::<AnimationGraph>(&bytes)?)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["anim.ron"]
+    }
+}
@@ -0,0 +1,216 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use std::collections::HashMap;
+
+use crate::state::ScheduleSet;
+
+/// Number of tiles spawned on each side of the center tile of a layer.
+const TILES_PER_SIDE: i32 = 2;
+
+/// Name of the LDtk level field used to pick a background from the `BackgroundLibrary`.
+const BACKGROUND_FIELD: &str = "Background";
+
+pub struct BackgroundPlugin;
+
+impl Plugin for BackgroundPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BackgroundLibrary>()
+            .init_resource::<CurrentBackground>()
+            .add_systems(
+                Update,
+                (select_level_background, update_parallax_layers)
+                    .chain()
+                    .after(ScheduleSet::PostTransformUpdate),
+            );
+    }
+}
+
+/// One image of a parallax background.
+#[derive(Clone, Debug)]
+pub struct ParallaxLayerConfig {
+    pub image: String,
+    /// How much the layer follows the camera. `0.0` keeps the layer fixed in the
+    /// world, `1.0` keeps it fixed on screen.
+    pub scroll_factor: Vec2,
+    pub offset: Vec2,
+    pub scale: f32,
+    pub z: f32,
+    pub color: Color,
+}
+
+/// Backgrounds that levels can select by name through their `Background` field.
+/// Levels without the field use the `default` entry.
+#[derive(Resource, Clone, Debug)]
+pub struct BackgroundLibrary {
+    pub backgrounds: HashMap<String, Vec<ParallaxLayerConfig>>,
+    pub default: String,
+}
+
+impl Default for BackgroundLibrary {
+    fn default() -> Self {
+        let mut backgrounds = HashMap::new();
+        backgrounds.insert(
+            "dusk".to_string(),
+            vec![
+                ParallaxLayerConfig {
+                    image: "Background_2.png".to_string(),
+                    scroll_factor: Vec2::new(0.9, 0.95),
+                    offset: Vec2::new(0.0, 40.0),
+                    scale: 3.0,
+                    z: -900.0,
+                    color: Color::rgb(0.55, 0.55, 0.7),
+                },
+                ParallaxLayerConfig {
+                    image: "Background_2.png".to_string(),
+                    scroll_factor: Vec2::new(0.7, 0.9),
+                    offset: Vec2::new(250.0, -60.0),
+                    scale: 2.0,
+                    z: -800.0,
+                    color: Color::rgba(1.0, 1.0, 1.0, 0.8),
+                },
+            ],
+        );
+        backgrounds.insert(
+            "night".to_string(),
+            vec![
+                ParallaxLayerConfig {
+                    image: "Background_2.png".to_string(),
+                    scroll_factor: Vec2::new(0.95, 0.95),
+                    offset: Vec2::new(0.0, 80.0),
+                    scale: 3.0,
+                    z: -900.0,
+                    color: Color::rgb(0.2, 0.22, 0.4),
+                },
+                ParallaxLayerConfig {
+                    image: "Background_2.png".to_string(),
+                    scroll_factor: Vec2::new(0.8, 0.9),
+                    offset: Vec2::new(-180.0, -40.0),
+                    scale: 2.5,
+                    z: -800.0,
+                    color: Color::rgba(0.35, 0.38, 0.6, 0.85),
+                },
+            ],
+        );
+        BackgroundLibrary {
+            backgrounds,
+            default: "dusk".to_string(),
+        }
+    }
+}
+
+/// Name of the background currently spawned.
+#[derive(Resource, Default, Debug)]
+pub struct CurrentBackground(Option<String>);
+
+#[derive(Component, Debug, Clone)]
+pub struct ParallaxLayer {
+    pub image: Handle<Image>,
+    pub scroll_factor: Vec2,
+    pub offset: Vec2,
+    pub scale: f32,
+}
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct ParallaxTile(i32);
+
+fn select_level_background(
+    mut commands: Commands,
+    level_selection: Res<LevelSelection>,
+    library: Res<BackgroundLibrary>,
+    mut current: ResMut<CurrentBackground>,
+    layer_query: Query<Entity, With<ParallaxLayer>>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    asset_server: Res<AssetServer>,
+) {
+    let Ok(project_handle) = ldtk_projects.get_single() else {
+        return;
+    };
+    let Some(ldtk_project) = ldtk_project_assets.get(project_handle) else {
+        return;
+    };
+    let Some(level) = ldtk_project
+        .iter_raw_levels()
+        .find(|level| level_selection.is_match(&LevelIndices::default(), level))
+    else {
+        return;
+    };
+
+    let name = match level.get_string_field(BACKGROUND_FIELD) {
+        Ok(name) if library.backgrounds.contains_key(name) => name.clone(),
+        _ => library.default.clone(),
+    };
+    if current.0.as_ref() == Some(&name) {
+        return;
+    }
+
+    for entity in layer_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for config in library.backgrounds.get(&name).into_iter().flatten() {
+        let image: Handle<Image> = asset_server.load(&config.image);
+        commands
+            .spawn((
+                ParallaxLayer {
+                    image: image.clone(),
+                    scroll_factor: config.scroll_factor,
+                    offset: config.offset,
+                    scale: config.scale,
+                },
+                SpatialBundle::from_transform(Transform::from_xyz(0.0, 0.0, config.z)),
+            ))
+            .with_children(|parent| {
+                for index in -TILES_PER_SIDE..=TILES_PER_SIDE {
+                    parent.spawn((
+                        ParallaxTile(index),
+                        SpriteBundle {
+                            texture: image.clone(),
+                            sprite: Sprite {
+                                color: config.color,
+                                ..default()
+                            },
+                            transform: Transform::from_scale(Vec3::new(
+                                config.scale,
+                                config.scale,
+                                1.0,
+                            )),
+                            ..default()
+                        },
+                    ));
+                }
+            });
+    }
+    current.0 = Some(name);
+}
+
+/// Positions each layer relative to the camera and wraps it horizontally so the
+/// tiles always cover the view.
+fn update_parallax_layers(
+    camera_query: Query<&Transform, (With<Camera>, Without<ParallaxLayer>, Without<ParallaxTile>)>,
+    mut layer_query: Query<(&ParallaxLayer, &mut Transform, &Children), Without<ParallaxTile>>,
+    mut tile_query: Query<(&ParallaxTile, &mut Transform), Without<ParallaxLayer>>,
+    images: Res<Assets<Image>>,
+) {
+    let Ok(camera_transform) = camera_query.get_single() else {
+        return;
+    };
+    let camera_pos = camera_transform.translation.truncate();
+
+    for (layer, mut layer_transform, children) in layer_query.iter_mut() {
+        let Some(image) = images.get(&layer.image) else {
+            continue;
+        };
+        let tile_width = image.width() as f32 * layer.scale;
+
+        let anchor = layer.offset + camera_pos * layer.scroll_factor;
+        let wraps = ((camera_pos.x - anchor.x) / tile_width).round();
+        layer_transform.translation.x = anchor.x + wraps * tile_width;
+        layer_transform.translation.y = anchor.y;
+
+        for &child in children.iter() {
+            if let Ok((tile, mut tile_transform)) = tile_query.get_mut(child) {
+                tile_transform.translation.x = tile.0 as f32 * tile_width;
+            }
+        }
+    }
+}
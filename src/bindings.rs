@@ -0,0 +1,436 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::settings::{read_config, write_config, SettingsError};
+use crate::state::ShutdownSet;
+
+pub struct BindingsPlugin;
+
+impl Plugin for BindingsPlugin {
+    fn build(&self, app: &mut App) {
+        let bindings = Bindings::load().unwrap_or_else(|error| {
+            warn!("Failed to load bindings, using defaults: {}", error);
+            Bindings::default()
+        });
+        app.insert_resource(bindings)
+            .add_systems(Update, save_bindings.run_if(resource_changed::<Bindings>()))
+            .add_systems(Last, save_bindings.in_set(ShutdownSet));
+    }
+}
+
+/// Everything a player can trigger. Physical inputs are mapped to actions by
+/// the `Bindings` resource.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    Jump,
+    ResetLevel,
+    OpenMenu,
+    ToggleFullscreen,
+    ZoomIn,
+    ZoomOut,
+    ZoomPreset(usize),
+    ResetZoom,
+    MenuUp,
+    MenuDown,
+    MenuLeft,
+    MenuRight,
+    MenuSelect,
+    MenuBack,
+    /// Debug builds only, see `MenuInputEvent::StepFrame`.
+    StepFrame,
+    /// Debug builds only, sends a `juice::MeleeHit` at the player until there
+    /// is a melee attack.
+    DebugHit,
+    /// Adds a player for the gamepad it was pressed on, see `coop::PlayerSlots`.
+    Join,
+    /// Starts or stops recording a replay, see `replay::ReplayPlugin`.
+    ToggleRecording,
+    PlayReplay,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(KeyCode),
+    Gamepad(GamepadButtonType),
+}
+
+/// Shapes the stick tilt after the dead zones are removed, both sides in `0..=1`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ResponseCurve {
+    Linear,
+    Quadratic,
+    Cubic,
+    Power(f32),
+}
+
+impl ResponseCurve {
+    pub fn apply(&self, value: f32) -> f32 {
+        match self {
+            ResponseCurve::Linear => value,
+            ResponseCurve::Quadratic => value * value,
+            ResponseCurve::Cubic => value * value * value,
+            ResponseCurve::Power(exponent) => value.powf(*exponent),
+        }
+    }
+}
+
+/// Analog stick handling of a gamepad.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct StickSettings {
+    /// Tilt below this is ignored, hides drift of worn sticks.
+    pub inner_dead_zone: f32,
+    /// Tilt above this counts as fully pushed, sticks rarely reach 1.0.
+    pub outer_dead_zone: f32,
+    pub curve: ResponseCurve,
+    /// Shaped tilt from which the player runs at full speed.
+    pub run_threshold: f32,
+    /// Movement strength below the run threshold, slow enough for the walk animation.
+    pub walk_strength: f32,
+}
+
+impl Default for StickSettings {
+    fn default() -> Self {
+        StickSettings {
+            inner_dead_zone: 0.2,
+            outer_dead_zone: 0.95,
+            curve: ResponseCurve::Quadratic,
+            run_threshold: 0.6,
+            walk_strength: 0.55,
+        }
+    }
+}
+
+impl StickSettings {
+    /// Tilt in `-1..=1` with the dead zones removed and the curve applied.
+    pub fn shape(&self, raw: f32) -> f32 {
+        let range = (self.outer_dead_zone - self.inner_dead_zone).max(f32::EPSILON);
+        let tilt = ((raw.abs() - self.inner_dead_zone) / range).clamp(0.0, 1.0);
+        self.curve.apply(tilt) * raw.signum()
+    }
+
+    /// Movement strength for a raw axis value, either walking or running.
+    pub fn movement_strength(&self, raw: f32) -> f32 {
+        let shaped = self.shape(raw);
+        if shaped == 0.0 {
+            0.0
+        } else if shaped.abs() >= self.run_threshold {
+            shaped.signum()
+        } else {
+            self.walk_strength * shaped.signum()
+        }
+    }
+}
+
+/// Stick settings for all gamepads, overridden per gamepad name.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct GamepadSettings {
+    pub default: StickSettings,
+    pub by_name: BTreeMap<String, StickSettings>,
+}
+
+impl GamepadSettings {
+    pub fn get(&self, name: Option<&str>) -> &StickSettings {
+        name.and_then(|name| self.by_name.get(name))
+            .unwrap_or(&self.default)
+    }
+}
+
+/// Bindings of every action, stored in `<config dir>/platformer/bindings.ron`.
+/// An action can have any number of bindings.
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Bindings {
+    pub actions: BTreeMap<Action, Vec<Binding>>,
+    #[serde(default)]
+    pub gamepads: GamepadSettings,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use Binding::{Gamepad, Key};
+        use GamepadButtonType as Button;
+
+        let mut actions = BTreeMap::from([
+            (
+                Action::MoveLeft,
+                vec![
+                    Key(KeyCode::A),
+                    Key(KeyCode::Left),
+                    Gamepad(Button::DPadLeft),
+                ],
+            ),
+            (
+                Action::MoveRight,
+                vec![
+                    Key(KeyCode::D),
+                    Key(KeyCode::Right),
+                    Gamepad(Button::DPadRight),
+                ],
+            ),
+            (
+                Action::Jump,
+                vec![
+                    Key(KeyCode::Space),
+                    Key(KeyCode::W),
+                    Key(KeyCode::Up),
+                    Gamepad(Button::South),
+                ],
+            ),
+            (
+                Action::ResetLevel,
+                vec![Key(KeyCode::R), Gamepad(Button::East)],
+            ),
+            (
+                Action::OpenMenu,
+                vec![Key(KeyCode::Escape), Gamepad(Button::Start)],
+            ),
+            (
+                Action::ToggleFullscreen,
+                vec![Key(KeyCode::F11), Gamepad(Button::Select)],
+            ),
+            (Action::ZoomIn, vec![Gamepad(Button::DPadUp)]),
+            (Action::ZoomOut, vec![Gamepad(Button::DPadDown)]),
+            (
+                Action::ResetZoom,
+                vec![Key(KeyCode::Key0), Gamepad(Button::RightThumb)],
+            ),
+            (
+                Action::MenuUp,
+                vec![Key(KeyCode::Up), Key(KeyCode::W), Gamepad(Button::DPadUp)],
+            ),
+            (
+                Action::MenuDown,
+                vec![
+                    Key(KeyCode::Down),
+                    Key(KeyCode::S),
+                    Gamepad(Button::DPadDown),
+                ],
+            ),
+            (
+                Action::MenuLeft,
+                vec![
+                    Key(KeyCode::Left),
+                    Key(KeyCode::A),
+                    Gamepad(Button::DPadLeft),
+                ],
+            ),
+            (
+                Action::MenuRight,
+                vec![
+                    Key(KeyCode::Right),
+                    Key(KeyCode::D),
+                    Gamepad(Button::DPadRight),
+                ],
+            ),
+            (
+                Action::MenuSelect,
+                vec![
+                    Key(KeyCode::Return),
+                    Key(KeyCode::Space),
+                    Gamepad(Button::South),
+                ],
+            ),
+            (
+                Action::MenuBack,
+                vec![
+                    Key(KeyCode::Escape),
+                    Gamepad(Button::Start),
+                    Gamepad(Button::East),
+                ],
+            ),
+            (Action::StepFrame, vec![Key(KeyCode::F10)]),
+            (Action::DebugHit, vec![Key(KeyCode::F9)]),
+            (Action::Join, vec![Gamepad(Button::North)]),
+            (Action::ToggleRecording, vec![Key(KeyCode::F5)]),
+            (Action::PlayReplay, vec![Key(KeyCode::F6)]),
+        ]);
+        let presets = [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4];
+        for (index, key) in presets.into_iter().enumerate() {
+            actions.insert(Action::ZoomPreset(index), vec![Key(key)]);
+        }
+        Bindings {
+            actions,
+            gamepads: GamepadSettings::default(),
+        }
+    }
+}
+
+impl Bindings {
+    const FILE_NAME: &'static str = "bindings.ron";
+
+    /// Reads the bindings file. Actions missing from the file keep their
+    /// default bindings, so new actions work with old files.
+    pub fn load() -> Result<Bindings, SettingsError> {
+        let mut bindings = Bindings::default();
+        if let Some(loaded) = read_config::<Bindings>(Self::FILE_NAME)? {
+            bindings.actions.extend(loaded.actions);
+            bindings.gamepads = loaded.gamepads;
+        }
+        Ok(bindings)
+    }
+
+    pub fn save(&self) -> Result<(), SettingsError> {
+        write_config(Self::FILE_NAME, self)
+    }
+
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.actions
+            .get(&action)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+}
+
+/// Device an action is read from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum InputSource {
+    Keyboard,
+    Gamepad(Gamepad),
+}
+
+/// Reads actions instead of physical inputs. Without a source, gamepad bindings
+/// match on any connected gamepad.
+#[derive(SystemParam)]
+pub struct ActionInput<'w> {
+    bindings: Res<'w, Bindings>,
+    keys: Res<'w, Input<KeyCode>>,
+    buttons: Res<'w, Input<GamepadButton>>,
+    gamepads: Res<'w, Gamepads>,
+}
+
+impl ActionInput<'_> {
+    /// The keyboard and every connected gamepad.
+    pub fn sources(&self) -> impl Iterator<Item = InputSource> + '_ {
+        std::iter::once(InputSource::Keyboard).chain(self.gamepads.iter().map(InputSource::Gamepad))
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.sources().any(|source| self.pressed_on(action, source))
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.sources()
+            .any(|source| self.just_pressed_on(action, source))
+    }
+
+    pub fn pressed_on(&self, action: Action, source: InputSource) -> bool {
+        self.bindings
+            .get(action)
+            .iter()
+            .any(|binding| match (binding, source) {
+                (Binding::Key(key), InputSource::Keyboard) => self.keys.pressed(*key),
+                (Binding::Gamepad(button_type), InputSource::Gamepad(gamepad)) => self
+                    .buttons
+                    .pressed(GamepadButton::new(gamepad, *button_type)),
+                _ => false,
+            })
+    }
+
+    pub fn just_pressed_on(&self, action: Action, source: InputSource) -> bool {
+        self.bindings
+            .get(action)
+            .iter()
+            .any(|binding| match (binding, source) {
+                (Binding::Key(key), InputSource::Keyboard) => self.keys.just_pressed(*key),
+                (Binding::Gamepad(button_type), InputSource::Gamepad(gamepad)) => self
+                    .buttons
+                    .just_pressed(GamepadButton::new(gamepad, *button_type)),
+                _ => false,
+            })
+    }
+}
+
+fn save_bindings(bindings: Res<Bindings>) {
+    if let Err(error) = bindings.save() {
+        warn!("Failed to save bindings: {}", error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-5,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn shape_removes_the_dead_zones() {
+        let stick = StickSettings::default();
+        assert_eq!(stick.shape(0.0), 0.0);
+        assert_eq!(stick.shape(0.1), 0.0);
+        assert_eq!(stick.shape(0.2), 0.0);
+        assert_eq!(stick.shape(0.95), 1.0);
+        assert_eq!(stick.shape(1.0), 1.0);
+        assert_eq!(stick.shape(-1.0), -1.0);
+    }
+
+    #[test]
+    fn shape_applies_the_curve_between_the_dead_zones() {
+        let mut stick = StickSettings::default();
+        // Halfway between the dead zones.
+        assert_near(stick.shape(0.575), 0.25);
+        assert_near(stick.shape(-0.575), -0.25);
+
+        stick.curve = ResponseCurve::Linear;
+        assert_near(stick.shape(0.575), 0.5);
+        stick.curve = ResponseCurve::Cubic;
+        assert_near(stick.shape(0.575), 0.125);
+        stick.curve = ResponseCurve::Power(0.5);
+        assert_near(stick.shape(0.575), 0.5f32.sqrt());
+    }
+
+    #[test]
+    fn shape_handles_overlapping_dead_zones() {
+        let stick = StickSettings {
+            inner_dead_zone: 0.5,
+            outer_dead_zone: 0.5,
+            ..Default::default()
+        };
+        assert_eq!(stick.shape(0.4), 0.0);
+        assert_eq!(stick.shape(0.6), 1.0);
+        assert_eq!(stick.shape(-0.6), -1.0);
+    }
+
+    #[test]
+    fn movement_strength_walks_below_the_run_threshold() {
+        let stick = StickSettings::default();
+        assert_eq!(stick.movement_strength(0.1), 0.0);
+        assert_eq!(stick.movement_strength(-0.1), 0.0);
+        assert_eq!(stick.movement_strength(0.575), stick.walk_strength);
+        assert_eq!(stick.movement_strength(-0.575), -stick.walk_strength);
+    }
+
+    #[test]
+    fn movement_strength_runs_from_the_run_threshold() {
+        let stick = StickSettings::default();
+        assert_eq!(stick.movement_strength(0.9), 1.0);
+        assert_eq!(stick.movement_strength(1.0), 1.0);
+        assert_eq!(stick.movement_strength(-0.9), -1.0);
+    }
+
+    #[test]
+    fn gamepad_settings_fall_back_to_the_default() {
+        let custom = StickSettings {
+            inner_dead_zone: 0.1,
+            ..Default::default()
+        };
+        let settings = GamepadSettings {
+            by_name: [("Worn Pad".to_string(), custom.clone())].into(),
+            ..Default::default()
+        };
+        assert_eq!(settings.get(Some("Worn Pad")), &custom);
+        assert_eq!(settings.get(Some("Other Pad")), &settings.default);
+        assert_eq!(settings.get(None), &settings.default);
+    }
+}
@@ -1,59 +1,274 @@
-use crate::player::Player;
+use crate::player::{Player, PlayerLanded};
 use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
 
-use crate::input::GameInputEvent;
+use crate::input::{GameInput, GameInputEvent};
 use crate::state::ScheduleSet;
 
 pub struct CameraPlugin;
 
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, (spawn_camera,)).add_systems(
-            Update,
-            ((move_camera, zoom_on_event).in_set(ScheduleSet::PostTransformUpdate),),
-        );
+        app.init_resource::<ZoomSettings>()
+            .init_resource::<ShakeSettings>()
+            .add_event::<CameraShake>()
+            .add_systems(Startup, (spawn_camera,))
+            .add_systems(
+                Update,
+                ((
+                    remove_shake_offset,
+                    (move_camera, (zoom_on_event, smooth_zoom).chain()),
+                    (shake_on_landing, apply_camera_shake).chain(),
+                )
+                    .chain()
+                    .in_set(ScheduleSet::PostTransformUpdate),),
+            );
     }
 }
 
-fn spawn_camera(mut commands: Commands) {
-    commands.spawn(Camera2dBundle {
-        transform: Transform::from_translation(Vec3::new(0.0, 0.0, 10.0)).with_scale(Vec3 {
-            x: 0.4,
-            y: 0.4,
-            z: 1.0,
-        }),
-        ..Default::default()
-    });
+/// Zoom limits and presets of the game camera. Zoom is expressed as the scale
+/// of the camera transform, so smaller values show less of the world.
+#[derive(Resource, Debug, Clone)]
+pub struct ZoomSettings {
+    pub min_scale: f32,
+    pub max_scale: f32,
+    pub default_scale: f32,
+    /// How fast the camera scale approaches its target, higher is snappier.
+    pub smoothing: f32,
+    /// Pixel perfect scales (one world pixel maps to a whole number of screen pixels).
+    pub presets: Vec<f32>,
+}
+
+impl Default for ZoomSettings {
+    fn default() -> Self {
+        ZoomSettings {
+            min_scale: 0.1,
+            max_scale: 1.0,
+            default_scale: 0.4,
+            smoothing: 12.0,
+            presets: vec![1.0 / 4.0, 1.0 / 3.0, 1.0 / 2.0, 1.0],
+        }
+    }
+}
+
+/// Margin in world units kept around the players when framing all of them.
+const FRAMING_MARGIN: f32 = 80.0;
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct CameraZoom {
+    pub target: f32,
+    /// Smallest scale that keeps every player on screen, overrides the target
+    /// while players are far apart.
+    pub framing: f32,
+}
+
+/// Request to shake the camera. `intensity` is added to the current trauma
+/// (clamped to 1.0) and fades out over `duration` seconds.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CameraShake {
+    pub intensity: f32,
+    pub duration: f32,
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct ShakeSettings {
+    /// Accessibility switch, no shake is applied while disabled.
+    pub enabled: bool,
+    pub max_offset: f32,
+    pub max_angle: f32,
+    /// Speed at which the noise driving the offsets is sampled.
+    pub frequency: f32,
+}
+
+impl Default for ShakeSettings {
+    fn default() -> Self {
+        ShakeSettings {
+            enabled: true,
+            max_offset: 12.0,
+            max_angle: 0.03,
+            frequency: 25.0,
+        }
+    }
+}
+
+/// Shake state of a camera. The offset applied in the last frame is stored so it
+/// can be removed again before `move_camera` runs, keeping the follow logic
+/// unaware of the shake.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct CameraTrauma {
+    pub trauma: f32,
+    decay: f32,
+    offset: Vec2,
+}
+
+fn spawn_camera(mut commands: Commands, zoom_settings: Res<ZoomSettings>) {
+    let scale = zoom_settings.default_scale;
+    commands.spawn((
+        Camera2dBundle {
+            transform: Transform::from_translation(Vec3::new(0.0, 0.0, 10.0)).with_scale(Vec3 {
+                x: scale,
+                y: scale,
+                z: 1.0,
+            }),
+            ..Default::default()
+        },
+        CameraZoom {
+            target: scale,
+            framing: 0.0,
+        },
+        CameraTrauma::default(),
+    ));
 }
 
 fn zoom_on_event(
     mut ev_scroll: EventReader<GameInputEvent>,
-    mut query: Query<&mut Transform, With<Camera>>,
+    mut query: Query<&mut CameraZoom>,
+    zoom_settings: Res<ZoomSettings>,
 ) {
     for ev in ev_scroll.read() {
-        match ev {
-            GameInputEvent::Zoom(y) => {
-                for mut transform in query.iter_mut() {
-                    transform.scale *= Vec3::new(1.0 + y * -0.1, 1.0 + y * -0.1, 1.0);
-                }
-            }
-            _ => {}
+        for mut zoom in query.iter_mut() {
+            let target = match ev.input {
+                GameInput::Zoom(y) => zoom.target * (y * -0.1).exp(),
+                GameInput::ZoomPreset(index) => match zoom_settings.presets.get(index) {
+                    Some(preset) => *preset,
+                    None => continue,
+                },
+                GameInput::ResetZoom => zoom_settings.default_scale,
+                _ => continue,
+            };
+            zoom.target = target.clamp(zoom_settings.min_scale, zoom_settings.max_scale);
         }
     }
 }
 
+/// Moves the camera scale toward its target zoom. The interpolation happens in
+/// log space so zooming in and out feel equally fast, and the scale snaps to the
+/// target once close enough to keep presets pixel perfect.
+fn smooth_zoom(
+    mut query: Query<(&mut Transform, &CameraZoom)>,
+    zoom_settings: Res<ZoomSettings>,
+    time: Res<Time>,
+) {
+    let t = 1.0 - (-zoom_settings.smoothing * time.delta_seconds()).exp();
+    for (mut transform, zoom) in query.iter_mut() {
+        let target = zoom.target.max(zoom.framing);
+        let current = transform.scale.x;
+        let scale = if (current - target).abs() < 0.0005 {
+            target
+        } else {
+            (current.ln() + (target.ln() - current.ln()) * t).exp()
+        };
+        transform.scale = Vec3::new(scale, scale, 1.0);
+    }
+}
+
+/// Follows the center of all players and zooms out as far as needed to keep
+/// every player on screen.
 fn move_camera(
     player_query: Query<&Transform, With<Player>>,
-    mut camera_query: Query<&mut Transform, (Without<Player>, With<Camera>)>,
+    mut camera_query: Query<(&mut Transform, &mut CameraZoom), (Without<Player>, With<Camera>)>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    zoom_settings: Res<ZoomSettings>,
     time: Res<Time>,
 ) {
-    for player_transform in player_query.iter() {
-        for mut camera_transform in camera_query.iter_mut() {
-            let camera_pos = camera_transform.translation;
-            camera_transform.translation +=
-                (player_transform.translation + Vec3::new(-700.0, -480.0, 0.0) - camera_pos)
-                    * time.delta_seconds()
-                    * 10.0;
+    let Some((min, max)) = player_query
+        .iter()
+        .map(|transform| transform.translation.truncate())
+        .fold(None, |bounds, position| match bounds {
+            Some((min, max)) => Some((position.min(min), position.max(max))),
+            None => Some((position, position)),
+        })
+    else {
+        return;
+    };
+    let center = ((min + max) / 2.0).extend(0.0);
+    let framing = match window_query.get_single() {
+        Ok(window) => {
+            let size = max - min + Vec2::splat(FRAMING_MARGIN * 2.0);
+            (size.x / window.width()).max(size.y / window.height())
         }
+        Err(_) => 0.0,
+    };
+
+    for (mut camera_transform, mut zoom) in camera_query.iter_mut() {
+        zoom.framing = framing.min(zoom_settings.max_scale);
+        let camera_pos = camera_transform.translation;
+        camera_transform.translation +=
+            (center + Vec3::new(-700.0, -480.0, 0.0) - camera_pos) * time.delta_seconds() * 10.0;
+    }
+}
+
+fn remove_shake_offset(mut query: Query<(&mut Transform, &mut CameraTrauma)>) {
+    for (mut transform, mut trauma) in query.iter_mut() {
+        transform.translation -= trauma.offset.extend(0.0);
+        transform.rotation = Quat::IDENTITY;
+        trauma.offset = Vec2::ZERO;
+    }
+}
+
+fn shake_on_landing(
+    mut landed_events: EventReader<PlayerLanded>,
+    mut shake_events: EventWriter<CameraShake>,
+) {
+    for event in landed_events.read() {
+        let intensity = ((event.impact_speed - 250.0) / 500.0).clamp(0.0, 0.6);
+        if intensity > 0.0 {
+            shake_events.send(CameraShake {
+                intensity,
+                duration: 0.4,
+            });
+        }
+    }
+}
+
+fn apply_camera_shake(
+    mut shake_events: EventReader<CameraShake>,
+    mut query: Query<(&mut Transform, &mut CameraTrauma)>,
+    shake_settings: Res<ShakeSettings>,
+    time: Res<Time>,
+) {
+    for event in shake_events.read() {
+        for (_, mut trauma) in query.iter_mut() {
+            trauma.trauma = (trauma.trauma + event.intensity).min(1.0);
+            trauma.decay = trauma.decay.max(trauma.trauma / event.duration.max(0.01));
+        }
+    }
+
+    let t = time.elapsed_seconds() * shake_settings.frequency;
+    for (mut transform, mut trauma) in query.iter_mut() {
+        trauma.trauma = (trauma.trauma - trauma.decay * time.delta_seconds()).max(0.0);
+        if trauma.trauma == 0.0 {
+            trauma.decay = 0.0;
+        }
+        if !shake_settings.enabled || trauma.trauma == 0.0 {
+            continue;
+        }
+
+        // Squaring the trauma makes small shakes subtle and big ones violent.
+        let shake = trauma.trauma * trauma.trauma;
+        trauma.offset = Vec2::new(value_noise(0, t), value_noise(1, t))
+            * shake_settings.max_offset
+            * shake
+            * transform.scale.x;
+        transform.translation += trauma.offset.extend(0.0);
+        transform.rotation =
+            Quat::from_rotation_z(value_noise(2, t) * shake_settings.max_angle * shake);
     }
 }
+
+/// Smooth 1D value noise in the range [-1, 1].
+fn value_noise(seed: u32, x: f32) -> f32 {
+    let cell = x.floor();
+    let f = x - cell;
+    let a = hash_to_unit(seed, cell as i32);
+    let b = hash_to_unit(seed, cell as i32 + 1);
+    a + (b - a) * f * f * (3.0 - 2.0 * f)
+}
+
+fn hash_to_unit(seed: u32, n: i32) -> f32 {
+    let mut h = (n as u32).wrapping_mul(0x27d4_eb2d) ^ seed.wrapping_mul(0x1656_67b1);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x2c1b_3c6d);
+    h ^= h >> 12;
+    h as f32 / u32::MAX as f32 * 2.0 - 1.0
+}
@@ -0,0 +1,174 @@
+use bevy::prelude::*;
+
+use crate::bindings::{Action, Binding, Bindings};
+use crate::menu_builder::{MenuActivated, MenuPage, MenuRoot, MenuSet, MenuValues};
+use crate::state::ScheduleSet;
+
+/// Actions shown on the controls page with their menu entry id and label key.
+/// Menu navigation is left out so a bad binding can not lock the player out.
+const REMAPPABLE: [(Action, &str, &str); 9] = [
+    (Action::MoveLeft, "bind_move_left", "controls.move_left"),
+    (Action::MoveRight, "bind_move_right", "controls.move_right"),
+    (Action::Jump, "bind_jump", "controls.jump"),
+    (
+        Action::ResetLevel,
+        "bind_reset_level",
+        "controls.reset_level",
+    ),
+    (Action::OpenMenu, "bind_open_menu", "controls.open_menu"),
+    (
+        Action::ToggleFullscreen,
+        "bind_fullscreen",
+        "controls.fullscreen",
+    ),
+    (Action::ZoomIn, "bind_zoom_in", "controls.zoom_in"),
+    (Action::ZoomOut, "bind_zoom_out", "controls.zoom_out"),
+    (Action::ResetZoom, "bind_reset_zoom", "controls.reset_zoom"),
+];
+
+const PAGE_TITLE: &str = "menu.controls";
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BindingCapture>().add_systems(
+            Update,
+            (
+                capture_binding
+                    .after(ScheduleSet::MenuInput)
+                    .before(MenuSet),
+                sync_controls_menu_values.before(MenuSet),
+                handle_controls_events.after(MenuSet),
+            ),
+        );
+    }
+}
+
+/// Action waiting for the next key or gamepad button press. Menu input is
+/// ignored while capturing, so the pressed input only ends up as the binding.
+#[derive(Resource, Debug, Default)]
+pub struct BindingCapture(pub Option<Action>);
+
+pub fn not_capturing(capture: Res<BindingCapture>) -> bool {
+    capture.0.is_none()
+}
+
+pub fn controls_page() -> MenuPage {
+    REMAPPABLE
+        .iter()
+        .fold(MenuPage::new(PAGE_TITLE), |page, (_, id, label)| {
+            page.button(*id, *label)
+        })
+        .button("controls_reset", "controls.reset")
+        .back("menu.back")
+}
+
+fn binding_name(binding: &Binding) -> String {
+    match binding {
+        Binding::Key(key) => format!("{:?}", key),
+        Binding::Gamepad(button) => format!("Pad {:?}", button),
+    }
+}
+
+fn is_conflicting(bindings: &Bindings, action: Action) -> bool {
+    bindings.get(action).iter().any(|binding| {
+        REMAPPABLE
+            .iter()
+            .any(|(other, _, _)| *other != action && bindings.get(*other).contains(binding))
+    })
+}
+
+fn sync_controls_menu_values(
+    bindings: Res<Bindings>,
+    capture: Res<BindingCapture>,
+    mut values: ResMut<MenuValues>,
+) {
+    if !bindings.is_changed() && !capture.is_changed() {
+        return;
+    }
+    for (action, id, _) in REMAPPABLE {
+        let text = if capture.0 == Some(action) {
+            "controls.press_key".to_string()
+        } else {
+            bindings
+                .get(action)
+                .iter()
+                .map(binding_name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        values.set_text(id, text);
+        values.set_highlighted(id, is_conflicting(&bindings, action));
+    }
+}
+
+/// The controls page is a submenu of the settings page, which is reachable from
+/// the title and the pause menu. Only activations of the menu that shows the
+/// controls page are handled.
+fn handle_controls_events(
+    mut activated_events: EventReader<MenuActivated>,
+    menu_query: Query<&MenuRoot>,
+    mut capture: ResMut<BindingCapture>,
+    mut bindings: ResMut<Bindings>,
+) {
+    for event in activated_events.read().filter(|event| {
+        menu_query
+            .iter()
+            .any(|menu| menu.id == event.menu && menu.page_title() == PAGE_TITLE)
+    }) {
+        if event.entry == "controls_reset" {
+            bindings.actions = Bindings::default().actions;
+            continue;
+        }
+        if let Some((action, _, _)) = REMAPPABLE.iter().find(|(_, id, _)| *id == event.entry) {
+            capture.0 = Some(*action);
+        }
+    }
+}
+
+/// Replaces the first binding of the same device with the pressed input. Escape
+/// cancels the capture.
+fn capture_binding(
+    mut capture: ResMut<BindingCapture>,
+    mut bindings: ResMut<Bindings>,
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+) {
+    let Some(action) = capture.0 else {
+        return;
+    };
+    if keys.just_pressed(KeyCode::Escape) {
+        capture.0 = None;
+        return;
+    }
+    let pressed = keys
+        .get_just_pressed()
+        .next()
+        .map(|key| Binding::Key(*key))
+        .or_else(|| {
+            buttons
+                .get_just_pressed()
+                .next()
+                .map(|button| Binding::Gamepad(button.button_type))
+        });
+    let Some(binding) = pressed else {
+        return;
+    };
+
+    capture.0 = None;
+    if bindings.get(action).contains(&binding) {
+        return;
+    }
+    let action_bindings = bindings.actions.entry(action).or_default();
+    let same_device = action_bindings.iter().position(|existing| {
+        matches!(
+            (existing, &binding),
+            (Binding::Key(_), Binding::Key(_)) | (Binding::Gamepad(_), Binding::Gamepad(_))
+        )
+    });
+    match same_device {
+        Some(index) => action_bindings[index] = binding,
+        None => action_bindings.push(binding),
+    }
+}
@@ -0,0 +1,248 @@
+use bevy::input::gamepad::{GamepadConnection, GamepadConnectionEvent};
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use std::collections::BTreeSet;
+
+use crate::bindings::{Action, ActionInput, InputSource};
+use crate::input::{GameInput, GameInputEvent, PendingInputs};
+use crate::player::{Player, PlayerId};
+use crate::replay::not_replaying;
+use crate::state::{AppState, ScheduleSet};
+use crate::world::PlayerBundle;
+
+/// Horizontal distance between a joined player and the player before it.
+const JOIN_SPACING: f32 = 24.0;
+
+pub struct CoopPlugin;
+
+impl Plugin for CoopPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PlayerSlots>()
+            .init_resource::<JoinedPlayers>()
+            .add_systems(
+                Update,
+                join_players
+                    .run_if(not_replaying)
+                    .in_set(ScheduleSet::HandleInput),
+            )
+            .add_systems(Update, leave_disconnected_players)
+            .add_systems(
+                FixedUpdate,
+                (update_joined_players, spawn_joined_players)
+                    .chain()
+                    .in_set(ScheduleSet::MainUpdate),
+            )
+            .add_systems(OnEnter(AppState::TitleScreen), clear_player_slots);
+    }
+}
+
+/// Gamepads of the players that joined, player `n` uses `gamepads[n - 1]`.
+/// Player 0 is the one placed in the LDtk level and reads the keyboard and every
+/// gamepad that has not joined. Slots of players that left stay empty until
+/// the next gamepad joins, so the other players keep their ids.
+#[derive(Resource, Debug, Default)]
+pub struct PlayerSlots {
+    gamepads: Vec<Option<Gamepad>>,
+}
+
+/// Players besides player 0 that are part of the simulation. Joining and
+/// leaving are simulation inputs so replays restore the same players, the
+/// gamepad assignment in `PlayerSlots` is not recorded.
+#[derive(Resource, Debug, Default)]
+pub struct JoinedPlayers(pub BTreeSet<PlayerId>);
+
+impl PlayerSlots {
+    /// Player controlled by the given device.
+    pub fn owner(&self, source: InputSource) -> PlayerId {
+        let InputSource::Gamepad(gamepad) = source else {
+            return PlayerId(0);
+        };
+        self.gamepads
+            .iter()
+            .position(|slot| *slot == Some(gamepad))
+            .map_or(PlayerId(0), |index| PlayerId(index + 1))
+    }
+
+    fn join(&mut self, gamepad: Gamepad) -> PlayerId {
+        let index = match self.gamepads.iter().position(Option::is_none) {
+            Some(index) => index,
+            None => {
+                self.gamepads.push(None);
+                self.gamepads.len() - 1
+            }
+        };
+        self.gamepads[index] = Some(gamepad);
+        PlayerId(index + 1)
+    }
+
+    fn leave(&mut self, gamepad: Gamepad) -> Option<PlayerId> {
+        let player = self.owner(InputSource::Gamepad(gamepad));
+        if player == PlayerId(0) {
+            return None;
+        }
+        self.gamepads[player.0 - 1] = None;
+        Some(player)
+    }
+}
+
+fn join_players(
+    actions: ActionInput,
+    mut slots: ResMut<PlayerSlots>,
+    mut pending: ResMut<PendingInputs>,
+) {
+    let joining: Vec<Gamepad> = actions
+        .sources()
+        .filter_map(|source| match source {
+            InputSource::Gamepad(gamepad) => Some(gamepad),
+            InputSource::Keyboard => None,
+        })
+        .filter(|gamepad| {
+            slots.owner(InputSource::Gamepad(*gamepad)) == PlayerId(0)
+                && actions.just_pressed_on(Action::Join, InputSource::Gamepad(*gamepad))
+        })
+        .collect();
+    for gamepad in joining {
+        let player = slots.join(gamepad);
+        info!("Gamepad {} joined as player {}", gamepad.id, player.0);
+        pending.0.push(GameInputEvent {
+            player,
+            input: GameInput::Join,
+        });
+    }
+}
+
+fn update_joined_players(
+    mut commands: Commands,
+    mut input_events: EventReader<GameInputEvent>,
+    mut joined: ResMut<JoinedPlayers>,
+    player_query: Query<(Entity, &PlayerId), With<Player>>,
+) {
+    for event in input_events.read() {
+        match event.input {
+            GameInput::Join => {
+                joined.0.insert(event.player);
+            }
+            GameInput::Leave => {
+                joined.0.remove(&event.player);
+                for (entity, player_id) in player_query.iter() {
+                    if *player_id == event.player {
+                        commands.entity(entity).despawn_recursive();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Spawns every joined player that has no entity yet next to player 0, as a
+/// sibling so it is respawned together with the level.
+fn spawn_joined_players(
+    mut commands: Commands,
+    joined: Res<JoinedPlayers>,
+    player_query: Query<(&PlayerId, &Transform, &Parent, &EntityInstance), With<Player>>,
+) {
+    let Some((_, lead_transform, parent, entity_instance)) = player_query
+        .iter()
+        .find(|(player_id, ..)| **player_id == PlayerId(0))
+    else {
+        return;
+    };
+    for player in joined.0.iter().copied() {
+        if player_query
+            .iter()
+            .any(|(player_id, ..)| *player_id == player)
+        {
+            continue;
+        }
+        let translation = lead_transform.translation + Vec3::X * JOIN_SPACING * player.0 as f32;
+        commands.entity(parent.get()).with_children(|layer| {
+            layer.spawn(PlayerBundle::joined(player, entity_instance, translation));
+        });
+    }
+}
+
+fn leave_disconnected_players(
+    mut connection_events: EventReader<GamepadConnectionEvent>,
+    mut slots: ResMut<PlayerSlots>,
+    mut pending: ResMut<PendingInputs>,
+) {
+    for event in connection_events.read() {
+        if event.connection != GamepadConnection::Disconnected {
+            continue;
+        }
+        let Some(player) = slots.leave(event.gamepad) else {
+            continue;
+        };
+        info!("Player {} left", player.0);
+        pending.0.push(GameInputEvent {
+            player,
+            input: GameInput::Leave,
+        });
+    }
+}
+
+fn clear_player_slots(mut slots: ResMut<PlayerSlots>, mut joined: ResMut<JoinedPlayers>) {
+    *slots = PlayerSlots::default();
+    *joined = JoinedPlayers::default();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pad(id: usize) -> InputSource {
+        InputSource::Gamepad(Gamepad::new(id))
+    }
+
+    #[test]
+    fn unjoined_devices_control_player_0() {
+        let slots = PlayerSlots::default();
+        assert_eq!(slots.owner(InputSource::Keyboard), PlayerId(0));
+        assert_eq!(slots.owner(pad(3)), PlayerId(0));
+    }
+
+    #[test]
+    fn gamepads_join_as_the_next_players() {
+        let mut slots = PlayerSlots::default();
+        assert_eq!(slots.join(Gamepad::new(3)), PlayerId(1));
+        assert_eq!(slots.join(Gamepad::new(0)), PlayerId(2));
+        assert_eq!(slots.owner(pad(3)), PlayerId(1));
+        assert_eq!(slots.owner(pad(0)), PlayerId(2));
+        assert_eq!(slots.owner(pad(1)), PlayerId(0));
+        assert_eq!(slots.owner(InputSource::Keyboard), PlayerId(0));
+    }
+
+    #[test]
+    fn leaving_keeps_the_ids_of_the_other_players() {
+        let mut slots = PlayerSlots::default();
+        slots.join(Gamepad::new(0));
+        slots.join(Gamepad::new(1));
+        slots.join(Gamepad::new(2));
+
+        assert_eq!(slots.leave(Gamepad::new(1)), Some(PlayerId(2)));
+        assert_eq!(slots.owner(pad(1)), PlayerId(0));
+        assert_eq!(slots.owner(pad(0)), PlayerId(1));
+        assert_eq!(slots.owner(pad(2)), PlayerId(3));
+    }
+
+    #[test]
+    fn joining_fills_the_first_empty_slot() {
+        let mut slots = PlayerSlots::default();
+        slots.join(Gamepad::new(0));
+        slots.join(Gamepad::new(1));
+        slots.leave(Gamepad::new(0));
+
+        assert_eq!(slots.join(Gamepad::new(5)), PlayerId(1));
+        assert_eq!(slots.join(Gamepad::new(6)), PlayerId(3));
+    }
+
+    #[test]
+    fn gamepads_that_did_not_join_can_not_leave() {
+        let mut slots = PlayerSlots::default();
+        slots.join(Gamepad::new(0));
+        assert_eq!(slots.leave(Gamepad::new(4)), None);
+        assert_eq!(slots.leave(Gamepad::new(0)), Some(PlayerId(1)));
+        assert_eq!(slots.leave(Gamepad::new(0)), None);
+    }
+}
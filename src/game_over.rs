@@ -0,0 +1,108 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::asset_loader::FontAssets;
+use crate::input::MenuInputEvent;
+use crate::localization::{Localization, LocalizedText};
+use crate::state::{AppState, ScheduleSet};
+
+pub struct GameOverPlugin;
+
+impl Plugin for GameOverPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::GameOver), spawn_game_over_screen)
+            .add_systems(
+                Update,
+                (handle_game_over_input,)
+                    .after(ScheduleSet::MenuInput)
+                    .run_if(in_state(AppState::GameOver)),
+            )
+            .add_systems(OnExit(AppState::GameOver), despawn_game_over_screen);
+    }
+}
+
+#[derive(Component)]
+struct GameOverScreen;
+
+fn spawn_game_over_screen(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    localization: Res<Localization>,
+) {
+    commands
+        .spawn((
+            GameOverScreen,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                LocalizedText("game_over.title".to_string()),
+                TextBundle::from_section(
+                    localization.get("game_over.title"),
+                    TextStyle {
+                        font: font_assets.menu_font.clone(),
+                        font_size: 80.0,
+                        color: Color::RED,
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::all(Val::Px(30.0)),
+                    ..default()
+                }),
+            ));
+            parent.spawn((
+                LocalizedText("game_over.hint".to_string()),
+                TextBundle::from_section(
+                    localization.get("game_over.hint"),
+                    TextStyle {
+                        font: font_assets.menu_font.clone(),
+                        font_size: 35.0,
+                        color: Color::WHITE,
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::all(Val::Px(15.0)),
+                    ..default()
+                }),
+            ));
+        });
+}
+
+/// Retries the current level or returns to the title screen.
+fn handle_game_over_input(
+    mut commands: Commands,
+    mut menu_events: EventReader<MenuInputEvent>,
+    level_query: Query<Entity, With<LevelIid>>,
+) {
+    for event in menu_events.read() {
+        match event {
+            MenuInputEvent::Select => {
+                for level_entity in &level_query {
+                    commands.entity(level_entity).insert(Respawn);
+                }
+                commands.insert_resource(NextState(Some(AppState::Playing)));
+            }
+            MenuInputEvent::CloseMenu => {
+                commands.insert_resource(NextState(Some(AppState::TitleScreen)));
+            }
+            _ => (),
+        }
+    }
+}
+
+fn despawn_game_over_screen(mut commands: Commands, query: Query<Entity, With<GameOverScreen>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
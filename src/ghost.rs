@@ -0,0 +1,276 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::player::{snapshot_physics_transforms, PhysicsInterpolation, Player, PlayerId};
+use crate::player_assets::PlayerSprite;
+use crate::settings::{read_config, write_config, SettingsError};
+use crate::state::{AppState, ScheduleSet};
+use crate::world::{LevelChanged, LevelFinished};
+
+/// Draws the ghost behind the player sprite.
+const GHOST_Z_OFFSET: f32 = 4.0;
+const GHOST_ALPHA: f32 = 0.35;
+
+pub struct GhostPlugin;
+
+impl Plugin for GhostPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GhostRecorder>()
+            .add_systems(
+                Update,
+                (restart_level_run, start_level_run)
+                    .chain()
+                    .after(ScheduleSet::HandleInput),
+            )
+            .add_systems(
+                FixedUpdate,
+                (finish_level_run, record_ghost_frame, play_ghost_frame)
+                    .chain()
+                    .in_set(ScheduleSet::RecordStep)
+                    .before(snapshot_physics_transforms),
+            )
+            .add_systems(OnEnter(AppState::TitleScreen), reset_ghosts);
+    }
+}
+
+/// Player 0 in one simulation step. The position is relative to the player's
+/// parent, the ghost is spawned next to the player so both share it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct GhostFrame {
+    pub position: Vec3,
+    pub index: usize,
+    pub flip_x: bool,
+}
+
+/// Run through one level, stored in `<config dir>/platformer/ghosts/<level iid>.ron`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct GhostRun {
+    pub frames: Vec<GhostFrame>,
+}
+
+impl GhostRun {
+    fn file_name(level: &str) -> String {
+        format!("ghosts/{}.ron", level)
+    }
+
+    pub fn load(level: &str) -> Result<Option<GhostRun>, SettingsError> {
+        read_config(&Self::file_name(level))
+    }
+
+    pub fn save(&self, level: &str) -> Result<(), SettingsError> {
+        write_config(&Self::file_name(level), self)
+    }
+}
+
+/// Attempt in the current level and the best run it competes against. A run
+/// is complete once player 0 reaches the goal or leaves the level.
+#[derive(Resource, Debug, Default)]
+pub struct GhostRecorder {
+    level: Option<String>,
+    current: Vec<GhostFrame>,
+    best: Option<GhostRun>,
+    tick: usize,
+    /// The goal was reached, frames after it are not part of the run.
+    finished: bool,
+}
+
+impl GhostRecorder {
+    /// Saves the current attempt if it is faster than the best run.
+    fn complete_run(&mut self) {
+        let Some(level) = self.level.as_ref().filter(|_| !self.current.is_empty()) else {
+            return;
+        };
+        let is_best = self
+            .best
+            .as_ref()
+            .map_or(true, |best| self.current.len() < best.frames.len());
+        if !is_best {
+            return;
+        }
+        let run = GhostRun {
+            frames: self.current.clone(),
+        };
+        match run.save(level) {
+            Ok(()) => info!("New best run in level {}", level),
+            Err(error) => warn!("Failed to save ghost: {}", error),
+        }
+        self.best = Some(run);
+    }
+}
+
+#[derive(Component)]
+pub struct Ghost;
+
+fn start_level_run(
+    mut commands: Commands,
+    mut level_changed_events: EventReader<LevelChanged>,
+    mut recorder: ResMut<GhostRecorder>,
+    ghost_query: Query<Entity, With<Ghost>>,
+) {
+    for event in level_changed_events.read() {
+        if !recorder.finished {
+            recorder.complete_run();
+        }
+
+        let best = GhostRun::load(&event.level).unwrap_or_else(|error| {
+            warn!("Failed to load ghost: {}", error);
+            None
+        });
+        *recorder = GhostRecorder {
+            level: Some(event.level.clone()),
+            current: Vec::new(),
+            best,
+            tick: 0,
+            finished: false,
+        };
+        for ghost in ghost_query.iter() {
+            commands.entity(ghost).despawn_recursive();
+        }
+    }
+}
+
+/// A new player 0 means the level was respawned, so the attempt starts over.
+/// Runs before `start_level_run`, so respawning in an earlier level does not
+/// count as completing the level the player died in.
+fn restart_level_run(
+    player_query: Query<&PlayerId, Added<Player>>,
+    mut recorder: ResMut<GhostRecorder>,
+) {
+    if player_query
+        .iter()
+        .any(|player_id| *player_id == PlayerId(0))
+    {
+        recorder.current.clear();
+        recorder.tick = 0;
+        recorder.finished = false;
+    }
+}
+
+/// Runs at the step the goal is reached, before that step is recorded.
+fn finish_level_run(
+    mut level_finished_events: EventReader<LevelFinished>,
+    mut recorder: ResMut<GhostRecorder>,
+) {
+    for event in level_finished_events.read() {
+        if recorder.finished || recorder.level.as_ref() != Some(&event.level) {
+            continue;
+        }
+        recorder.complete_run();
+        recorder.finished = true;
+    }
+}
+
+fn record_ghost_frame(
+    mut recorder: ResMut<GhostRecorder>,
+    player_query: Query<(&PlayerId, &Transform, &Children), With<Player>>,
+    sprite_query: Query<&TextureAtlasSprite, With<PlayerSprite>>,
+) {
+    if recorder.level.is_none() || recorder.finished {
+        return;
+    }
+    for (player_id, transform, children) in player_query.iter() {
+        if *player_id != PlayerId(0) {
+            continue;
+        }
+        let Some(sprite) = sprite_query.iter_many(children).next() else {
+            continue;
+        };
+        recorder.current.push(GhostFrame {
+            position: transform.translation,
+            index: sprite.index,
+            flip_x: sprite.flip_x,
+        });
+    }
+}
+
+/// Shows the best run at the step the current attempt is at, and hides the
+/// ghost once the best run is over. The ghost is interpolated between steps
+/// like the player, and jumps without interpolating when an attempt starts.
+fn play_ghost_frame(
+    mut commands: Commands,
+    mut recorder: ResMut<GhostRecorder>,
+    player_query: Query<(&PlayerId, &Parent, &Children), With<Player>>,
+    sprite_query: Query<
+        (&Handle<TextureAtlas>, &TextureAtlasSprite, &Transform),
+        (With<PlayerSprite>, Without<Ghost>),
+    >,
+    mut ghost_query: Query<
+        (
+            &mut Transform,
+            &mut TextureAtlasSprite,
+            &mut Visibility,
+            &mut PhysicsInterpolation,
+        ),
+        (With<Ghost>, Without<PlayerSprite>),
+    >,
+) {
+    let Some(frame) = recorder
+        .best
+        .as_ref()
+        .and_then(|best| best.frames.get(recorder.tick))
+        .copied()
+    else {
+        for (_, _, mut visibility, _) in ghost_query.iter_mut() {
+            *visibility = Visibility::Hidden;
+        }
+        return;
+    };
+    let starting = recorder.tick == 0;
+    recorder.tick += 1;
+
+    let Some((_, parent, children)) = player_query
+        .iter()
+        .find(|(player_id, ..)| **player_id == PlayerId(0))
+    else {
+        return;
+    };
+    let Some((atlas, player_sprite, sprite_transform)) = sprite_query.iter_many(children).next()
+    else {
+        return;
+    };
+
+    let translation = frame.position + Vec3::Z * GHOST_Z_OFFSET;
+    let sprite = TextureAtlasSprite {
+        index: frame.index,
+        flip_x: frame.flip_x,
+        anchor: player_sprite.anchor.clone(),
+        color: Color::WHITE.with_a(GHOST_ALPHA),
+        ..Default::default()
+    };
+    match ghost_query.get_single_mut() {
+        Ok((mut transform, mut ghost_sprite, mut visibility, mut interpolation)) => {
+            if starting {
+                interpolation.reset();
+            }
+            transform.translation = translation;
+            *ghost_sprite = sprite;
+            *visibility = Visibility::Inherited;
+        }
+        Err(_) => {
+            commands.entity(parent.get()).with_children(|layer| {
+                layer.spawn((
+                    Ghost,
+                    SpriteSheetBundle {
+                        transform: Transform::from_translation(translation)
+                            .with_scale(sprite_transform.scale),
+                        sprite,
+                        texture_atlas: atlas.clone(),
+                        ..Default::default()
+                    },
+                    PhysicsInterpolation::default(),
+                ));
+            });
+        }
+    }
+}
+
+fn reset_ghosts(
+    mut commands: Commands,
+    mut recorder: ResMut<GhostRecorder>,
+    ghost_query: Query<Entity, With<Ghost>>,
+) {
+    *recorder = GhostRecorder::default();
+    for ghost in ghost_query.iter() {
+        commands.entity(ghost).despawn_recursive();
+    }
+}
@@ -1,15 +1,34 @@
 use bevy::input::mouse::MouseWheel;
 use bevy::prelude::*;
 
+use crate::bindings::{Action, ActionInput, Bindings, InputSource};
+use crate::controls::not_capturing;
+use crate::coop::PlayerSlots;
+use crate::player::PlayerId;
+use crate::replay::{not_replaying, players_ready};
 use crate::state::ScheduleSet;
 
-#[derive(Debug, Event)]
-pub enum GameInputEvent {
+/// Input of one player. Inputs that are not tied to a player, like zooming,
+/// carry the player whose device triggered them.
+#[derive(Debug, Clone, Copy, PartialEq, Event)]
+pub struct GameInputEvent {
+    pub player: PlayerId,
+    pub input: GameInput,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameInput {
     Zoom(f32),
+    ZoomPreset(usize),
+    ResetZoom,
     MoveLeft(f32),
     MoveRight(f32),
     Jump,
     ResetLevel,
+    /// The player joined with a gamepad and is spawned next to player 0.
+    Join,
+    /// The gamepad of the player disconnected and its entity is removed.
+    Leave,
     OpenMenu,
     ToggleFullscreen,
 }
@@ -18,67 +37,70 @@ pub enum GameInputEvent {
 pub enum MenuInputEvent {
     Up,
     Down,
+    Left,
+    Right,
     Select,
     CloseMenu,
+    /// Debug only: advance the paused simulation by one step.
+    StepFrame,
 }
 
 pub struct InputPlugin;
 
+/// Simulation inputs triggered by a press, sent at the next simulation step.
+/// Events sent in `Update` are gone before a step runs in frames without one,
+/// so presses are kept here until a step takes them. While the players are
+/// respawning they are held back as well, since replays skip those steps.
+#[derive(Resource, Debug, Default)]
+pub struct PendingInputs(pub Vec<GameInputEvent>);
+
 impl Plugin for InputPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<GameInputEvent>()
             .add_event::<MenuInputEvent>()
+            .init_resource::<PendingInputs>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    sample_movement_actions,
+                    sample_gamepad_movement,
+                    sample_pending_inputs.run_if(players_ready),
+                )
+                    .run_if(not_replaying)
+                    .in_set(ScheduleSet::SampleInput),
+            )
             .add_systems(
                 Update,
                 (
-                    handle_keyboard_input,
+                    handle_game_actions,
+                    handle_reset_action.run_if(not_replaying),
                     handle_mouse_wheel_input,
-                    gamepad_system,
                 )
                     .in_set(ScheduleSet::HandleInput),
             )
             .add_systems(
                 Update,
-                (handle_menu_event_keyboard, handle_menu_event_controler)
-                    .in_set(ScheduleSet::PauseMenu),
+                (handle_menu_actions.run_if(not_capturing),).in_set(ScheduleSet::MenuInput),
             );
     }
 }
 
-fn handle_menu_event_keyboard(
-    mut menu_input_event: EventWriter<MenuInputEvent>,
-    keyboard_input: Res<Input<KeyCode>>,
-) {
-    keyboard_input
-        .get_just_pressed()
-        .filter_map(|key| match key {
-            KeyCode::Up => Some(MenuInputEvent::Up),
-            KeyCode::Down => Some(MenuInputEvent::Down),
-            KeyCode::Return => Some(MenuInputEvent::Select),
-            KeyCode::Escape => Some(MenuInputEvent::CloseMenu),
-            _ => None,
-        })
-        .for_each(|event| menu_input_event.send(event));
-}
+fn handle_menu_actions(mut menu_input_event: EventWriter<MenuInputEvent>, actions: ActionInput) {
+    [
+        (Action::MenuUp, MenuInputEvent::Up),
+        (Action::MenuDown, MenuInputEvent::Down),
+        (Action::MenuLeft, MenuInputEvent::Left),
+        (Action::MenuRight, MenuInputEvent::Right),
+        (Action::MenuSelect, MenuInputEvent::Select),
+        (Action::MenuBack, MenuInputEvent::CloseMenu),
+    ]
+    .into_iter()
+    .filter(|(action, _)| actions.just_pressed(*action))
+    .for_each(|(_, event)| menu_input_event.send(event));
 
-fn handle_menu_event_controler(
-    button_inputs: Res<Input<GamepadButton>>,
-    mut input_event: EventWriter<MenuInputEvent>,
-) {
-    button_inputs
-        .get_just_pressed()
-        .for_each(|button| match button {
-            GamepadButton {
-                gamepad: _,
-                button_type,
-            } => match button_type {
-                GamepadButtonType::DPadUp => input_event.send(MenuInputEvent::Up),
-                GamepadButtonType::DPadDown => input_event.send(MenuInputEvent::Down),
-                GamepadButtonType::South => input_event.send(MenuInputEvent::Select),
-                GamepadButtonType::Start => input_event.send(MenuInputEvent::CloseMenu),
-                _ => {}
-            },
-        });
+    if cfg!(debug_assertions) && actions.just_pressed(Action::StepFrame) {
+        menu_input_event.send(MenuInputEvent::StepFrame);
+    }
 }
 
 fn handle_mouse_wheel_input(
@@ -86,78 +108,131 @@ fn handle_mouse_wheel_input(
     mut ev_scroll: EventReader<MouseWheel>,
 ) {
     for ev in ev_scroll.read() {
-        input_event.send(GameInputEvent::Zoom(ev.y));
+        input_event.send(GameInputEvent {
+            player: PlayerId::default(),
+            input: GameInput::Zoom(ev.y),
+        });
     }
 }
 
-fn handle_keyboard_input(
+/// Movement is sampled once per simulation step, so every step sees the held
+/// inputs exactly once no matter how many steps run in a frame. Each device
+/// moves the player it belongs to.
+fn sample_movement_actions(
     mut input_event: EventWriter<GameInputEvent>,
-    keyboard_input: Res<Input<KeyCode>>,
+    actions: ActionInput,
+    slots: Res<PlayerSlots>,
 ) {
-    keyboard_input
-        .get_pressed()
-        .filter_map(|key| match key {
-            KeyCode::A => Some(GameInputEvent::MoveLeft(1.0)),
-            KeyCode::D => Some(GameInputEvent::MoveRight(1.0)),
-            KeyCode::Space => Some(GameInputEvent::Jump),
-            _ => None,
-        })
-        .for_each(|event| input_event.send(event));
-    keyboard_input
-        .get_just_pressed()
-        .filter_map(|key| match key {
-            KeyCode::R => Some(GameInputEvent::ResetLevel),
-            KeyCode::Escape => Some(GameInputEvent::OpenMenu),
-            KeyCode::F11 => Some(GameInputEvent::ToggleFullscreen),
-            _ => None,
-        })
-        .for_each(|event| input_event.send(event));
+    for source in actions.sources() {
+        let player = slots.owner(source);
+        [
+            (Action::MoveLeft, GameInput::MoveLeft(1.0)),
+            (Action::MoveRight, GameInput::MoveRight(1.0)),
+            (Action::Jump, GameInput::Jump),
+        ]
+        .into_iter()
+        .filter(|(action, _)| actions.pressed_on(*action, source))
+        .for_each(|(_, input)| input_event.send(GameInputEvent { player, input }));
+    }
 }
 
-fn gamepad_system(
-    gamepads: Res<Gamepads>,
-    button_inputs: Res<Input<GamepadButton>>,
-    axes: Res<Axis<GamepadAxis>>,
+/// Sends each triggered action once, for the first device that triggered it.
+fn handle_game_actions(
     mut input_event: EventWriter<GameInputEvent>,
+    actions: ActionInput,
+    slots: Res<PlayerSlots>,
     time: Res<Time>,
 ) {
-    for gamepad in gamepads.iter() {
-        button_inputs.get_pressed().for_each(|button| match button {
-            GamepadButton {
-                gamepad: _,
-                button_type,
-            } => match button_type {
-                GamepadButtonType::South => input_event.send(GameInputEvent::Jump),
-                GamepadButtonType::DPadUp => {
-                    input_event.send(GameInputEvent::Zoom(10.0 * time.delta_seconds()))
+    let triggered_by = |action: Action, continuous: bool| {
+        actions
+            .sources()
+            .find(|source| {
+                if continuous {
+                    actions.pressed_on(action, *source)
+                } else {
+                    actions.just_pressed_on(action, *source)
                 }
-                GamepadButtonType::DPadDown => {
-                    input_event.send(GameInputEvent::Zoom(-10.0 * time.delta_seconds()))
-                }
-                _ => {}
-            },
+            })
+            .map(|source| slots.owner(source))
+    };
+
+    [
+        (Action::OpenMenu, GameInput::OpenMenu),
+        (Action::ToggleFullscreen, GameInput::ToggleFullscreen),
+        (Action::ZoomPreset(0), GameInput::ZoomPreset(0)),
+        (Action::ZoomPreset(1), GameInput::ZoomPreset(1)),
+        (Action::ZoomPreset(2), GameInput::ZoomPreset(2)),
+        (Action::ZoomPreset(3), GameInput::ZoomPreset(3)),
+        (Action::ResetZoom, GameInput::ResetZoom),
+    ]
+    .into_iter()
+    .filter_map(|(action, input)| Some((triggered_by(action, false)?, input)))
+    .for_each(|(player, input)| input_event.send(GameInputEvent { player, input }));
+
+    if let Some(player) = triggered_by(Action::ZoomIn, true) {
+        input_event.send(GameInputEvent {
+            player,
+            input: GameInput::Zoom(10.0 * time.delta_seconds()),
+        });
+    }
+    if let Some(player) = triggered_by(Action::ZoomOut, true) {
+        input_event.send(GameInputEvent {
+            player,
+            input: GameInput::Zoom(-10.0 * time.delta_seconds()),
+        });
+    }
+}
+
+/// Resetting the level is a simulation input, so it is taken from the replay
+/// during playback like movement.
+fn handle_reset_action(
+    mut pending: ResMut<PendingInputs>,
+    actions: ActionInput,
+    slots: Res<PlayerSlots>,
+) {
+    if let Some(source) = actions
+        .sources()
+        .find(|source| actions.just_pressed_on(Action::ResetLevel, *source))
+    {
+        pending.0.push(GameInputEvent {
+            player: slots.owner(source),
+            input: GameInput::ResetLevel,
         });
-        button_inputs
-            .get_just_pressed()
-            .for_each(|button| match button {
-                GamepadButton {
-                    gamepad: _,
-                    button_type,
-                } => match button_type {
-                    GamepadButtonType::East => input_event.send(GameInputEvent::ResetLevel),
-                    GamepadButtonType::Start => input_event.send(GameInputEvent::OpenMenu),
-                    GamepadButtonType::Select => input_event.send(GameInputEvent::ToggleFullscreen),
-                    _ => {}
-                },
-            });
+    }
+}
 
+fn sample_pending_inputs(
+    mut pending: ResMut<PendingInputs>,
+    mut input_event: EventWriter<GameInputEvent>,
+) {
+    input_event.send_batch(pending.0.drain(..));
+}
+
+/// Analog movement from the left stick, buttons are handled by the action map.
+/// Gamepads that do not report the axis are treated as centered.
+fn sample_gamepad_movement(
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    bindings: Res<Bindings>,
+    slots: Res<PlayerSlots>,
+    mut input_event: EventWriter<GameInputEvent>,
+) {
+    for gamepad in gamepads.iter() {
+        let stick = bindings.gamepads.get(gamepads.name(gamepad));
         let left_stick_x = axes
             .get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX))
-            .unwrap();
-        if left_stick_x > 0.2 {
-            input_event.send(GameInputEvent::MoveRight(left_stick_x));
-        } else if left_stick_x < -0.2 {
-            input_event.send(GameInputEvent::MoveLeft(-left_stick_x));
-        }
+            .unwrap_or(0.0);
+        let strength = stick.movement_strength(left_stick_x);
+        let input = if strength > 0.0 {
+            GameInput::MoveRight(strength)
+        } else if strength < 0.0 {
+            GameInput::MoveLeft(-strength)
+        } else {
+            continue;
+        };
+        input_event.send(GameInputEvent {
+            player: slots.owner(InputSource::Gamepad(gamepad)),
+            input,
+        });
     }
 }
@@ -0,0 +1,143 @@
+use bevy::prelude::*;
+
+use crate::bindings::{Action, ActionInput};
+use crate::camera::CameraShake;
+use crate::particles::{ParticleEffect, SpawnParticles};
+use crate::player::{Player, PlayerJumped, PlayerLanded, PLAYER_FEET_OFFSET};
+use crate::player_assets::PlayerSprite;
+use crate::state::{HitStop, ScheduleSet};
+
+pub struct JuicePlugin;
+
+impl Plugin for JuicePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<MeleeHit>()
+            .add_systems(Update, (apply_melee_hits,).in_set(ScheduleSet::CheckMenu))
+            .add_systems(Update, (debug_melee_hit,).in_set(ScheduleSet::HandleInput))
+            .add_systems(
+                Update,
+                (
+                    attach_squash_stretch,
+                    squash_on_jump_and_landing,
+                    update_squash_stretch,
+                )
+                    .chain()
+                    .in_set(ScheduleSet::PostTransformUpdate),
+            );
+    }
+}
+
+/// Sent when a melee attack connects. Freezes the simulation for a few frames and
+/// adds shake and sparks at the point of impact.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct MeleeHit {
+    pub position: Vec3,
+    pub hit_stop_frames: u32,
+}
+
+/// Damped spring deforming a sprite. `amount` is multiplied with the base scale,
+/// `(1.0, 1.0)` being the rest state.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct SquashStretch {
+    pub amount: Vec2,
+    pub velocity: Vec2,
+    base_scale: Vec3,
+    base_translation: Vec3,
+}
+
+impl SquashStretch {
+    const STIFFNESS: f32 = 350.0;
+    const DAMPING: f32 = 18.0;
+
+    pub fn deform(&mut self, amount: Vec2) {
+        self.amount = amount;
+        self.velocity = Vec2::ZERO;
+    }
+}
+
+fn attach_squash_stretch(
+    mut commands: Commands,
+    sprite_query: Query<(Entity, &Transform), Added<PlayerSprite>>,
+) {
+    for (entity, transform) in sprite_query.iter() {
+        commands.entity(entity).insert(SquashStretch {
+            amount: Vec2::ONE,
+            velocity: Vec2::ZERO,
+            base_scale: transform.scale,
+            base_translation: transform.translation,
+        });
+    }
+}
+
+fn squash_on_jump_and_landing(
+    mut jumped_events: EventReader<PlayerJumped>,
+    mut landed_events: EventReader<PlayerLanded>,
+    mut sprite_query: Query<(&mut SquashStretch, &Parent)>,
+) {
+    for event in jumped_events.read() {
+        for (mut squash, parent) in sprite_query.iter_mut() {
+            if parent.get() == event.entity {
+                squash.deform(Vec2::new(0.75, 1.3));
+            }
+        }
+    }
+    for event in landed_events.read() {
+        let strength = (event.impact_speed / 600.0).clamp(0.1, 0.4);
+        for (mut squash, parent) in sprite_query.iter_mut() {
+            if parent.get() == event.entity {
+                squash.deform(Vec2::new(1.0 + strength, 1.0 - strength));
+            }
+        }
+    }
+}
+
+fn update_squash_stretch(mut query: Query<(&mut SquashStretch, &mut Transform)>, time: Res<Time>) {
+    let delta = time.delta_seconds();
+    for (mut squash, mut transform) in query.iter_mut() {
+        let acceleration = (Vec2::ONE - squash.amount) * SquashStretch::STIFFNESS
+            - squash.velocity * SquashStretch::DAMPING;
+        squash.velocity += acceleration * delta;
+        let velocity = squash.velocity;
+        squash.amount += velocity * delta;
+
+        transform.scale = squash.base_scale * squash.amount.extend(1.0);
+        // Keep the feet in place while the sprite is deformed.
+        transform.translation.y =
+            squash.base_translation.y - PLAYER_FEET_OFFSET * (1.0 - squash.amount.y);
+    }
+}
+
+fn debug_melee_hit(
+    actions: ActionInput,
+    player_query: Query<&GlobalTransform, With<Player>>,
+    mut hit_events: EventWriter<MeleeHit>,
+) {
+    if !cfg!(debug_assertions) || !actions.just_pressed(Action::DebugHit) {
+        return;
+    }
+    for transform in player_query.iter() {
+        hit_events.send(MeleeHit {
+            position: transform.translation(),
+            hit_stop_frames: 6,
+        });
+    }
+}
+
+fn apply_melee_hits(
+    mut hit_events: EventReader<MeleeHit>,
+    mut hit_stop: ResMut<HitStop>,
+    mut shake_events: EventWriter<CameraShake>,
+    mut spawn_events: EventWriter<SpawnParticles>,
+) {
+    for event in hit_events.read() {
+        hit_stop.request(event.hit_stop_frames);
+        shake_events.send(CameraShake {
+            intensity: 0.3,
+            duration: 0.25,
+        });
+        spawn_events.send(SpawnParticles {
+            position: event.position,
+            effect: ParticleEffect::hit(),
+        });
+    }
+}
@@ -0,0 +1,259 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::ldtk::{FieldValue, Level};
+use bevy_ecs_ldtk::prelude::*;
+use bevy_ecs_ldtk::utils::ldtk_pixel_coords_to_translation_pivoted;
+
+use crate::localization::Localization;
+use crate::menu_builder::{MenuActivated, MenuPage, MenuRoot, MenuSet, MenuValues};
+use crate::save::{ActiveSave, PendingPlacement, SaveSlots};
+use crate::speedrun::format_time;
+use crate::state::AppState;
+use crate::world::LevelAssets;
+
+const PAGE_TITLE: &str = "level_select.title";
+/// Optional level fields, a String and a FilePath relative to the LDtk project.
+const DISPLAY_NAME_FIELD: &str = "DisplayName";
+const THUMBNAIL_FIELD: &str = "Thumbnail";
+/// Entities a level is entered at, in order of preference.
+const ENTRY_ENTITIES: [&str; 2] = ["Entry", "Player"];
+
+pub struct LevelSelectPlugin;
+
+impl Plugin for LevelSelectPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LevelList>()
+            .add_systems(OnExit(AppState::Loading), collect_levels)
+            .add_systems(OnEnter(AppState::TitleScreen), spawn_thumbnail)
+            .add_systems(
+                Update,
+                (
+                    sync_level_select_values.before(MenuSet),
+                    (handle_level_select_events, update_thumbnail).after(MenuSet),
+                )
+                    .run_if(in_state(AppState::TitleScreen)),
+            )
+            .add_systems(OnExit(AppState::TitleScreen), despawn_thumbnail);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LevelInfo {
+    pub iid: String,
+    /// `DisplayName` field of the level, its identifier if not set.
+    pub name: String,
+    pub thumbnail: Option<String>,
+}
+
+/// Levels of the LDtk project in project order.
+#[derive(Resource, Debug, Default)]
+pub struct LevelList(pub Vec<LevelInfo>);
+
+fn string_field(level: &Level, identifier: &str) -> Option<String> {
+    level
+        .field_instances
+        .iter()
+        .find(|field| field.identifier == identifier)
+        .and_then(|field| match &field.value {
+            FieldValue::String(value) | FieldValue::FilePath(value) => value.clone(),
+            _ => None,
+        })
+        .filter(|value| !value.is_empty())
+}
+
+fn find_entity<'a>(level: &'a Level, identifier: &str) -> Option<&'a EntityInstance> {
+    level
+        .layer_instances
+        .iter()
+        .flatten()
+        .flat_map(|layer| &layer.entity_instances)
+        .find(|entity| entity.identifier == identifier)
+}
+
+/// Position at which player 0 enters the level, relative to the level the LDtk
+/// player is placed in, since that is the parent space of player 0. Levels
+/// without an entry entity are entered at their center.
+pub fn entry_point(project: &LdtkProject, iid: &str) -> Option<Vec2> {
+    let level = project.get_raw_level_by_iid(iid)?;
+    let home = project
+        .iter_raw_levels()
+        .find(|level| find_entity(level, "Player").is_some())?;
+
+    let local = ENTRY_ENTITIES
+        .iter()
+        .find_map(|identifier| find_entity(level, identifier))
+        .map(|entity| {
+            ldtk_pixel_coords_to_translation_pivoted(
+                entity.px,
+                level.px_hei,
+                IVec2::new(entity.width, entity.height),
+                entity.pivot,
+            )
+        })
+        .unwrap_or(Vec2::new(level.px_wid as f32, level.px_hei as f32) / 2.0);
+    let offset = Vec2::new(
+        (level.world_x - home.world_x) as f32,
+        (home.world_y + home.px_hei - level.world_y - level.px_hei) as f32,
+    );
+    Some(local + offset)
+}
+
+pub fn level_select_page(levels: &LevelList) -> MenuPage {
+    MenuPage::new(PAGE_TITLE)
+        .choice(
+            "level",
+            "level_select.level",
+            levels.0.iter().map(|level| level.name.clone()),
+        )
+        .button("level_start", "level_select.start")
+        .back("menu.back")
+}
+
+fn collect_levels(
+    mut levels: ResMut<LevelList>,
+    level_assets: Res<LevelAssets>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+) {
+    let Some(project) = ldtk_project_assets.get(&level_assets.project) else {
+        return;
+    };
+    levels.0 = project
+        .iter_raw_levels()
+        .map(|level| LevelInfo {
+            iid: level.iid.clone(),
+            name: string_field(level, DISPLAY_NAME_FIELD)
+                .unwrap_or_else(|| level.identifier.clone()),
+            thumbnail: string_field(level, THUMBNAIL_FIELD),
+        })
+        .collect();
+}
+
+/// Shows completion and best time of the selected level from the latest save
+/// next to the start button.
+fn sync_level_select_values(
+    levels: Res<LevelList>,
+    slots: Res<SaveSlots>,
+    localization: Res<Localization>,
+    mut values: ResMut<MenuValues>,
+    mut shown: Local<String>,
+) {
+    let Some(level) = levels.0.get(values.get_index("level")) else {
+        return;
+    };
+    let record = slots
+        .latest()
+        .and_then(|slot| slots.slots[slot].as_ref()?.levels.get(&level.iid))
+        .cloned()
+        .unwrap_or_default();
+    let text = match (record.completed, record.best_time) {
+        (true, Some(best)) => format!(
+            "{}, {} {}",
+            localization.get("level_select.completed"),
+            localization.get("level_select.best"),
+            format_time(best)
+        ),
+        (true, None) => localization.get("level_select.completed"),
+        (false, _) => localization.get("level_select.open"),
+    };
+    if *shown != text {
+        values.set_text("level_start", text.clone());
+        *shown = text;
+    }
+}
+
+/// Starts the selected level as a practice game of the latest save. Completing
+/// levels updates their records in the save, the progress of the save is kept.
+/// Without a save nothing is kept.
+#[allow(clippy::too_many_arguments)]
+fn handle_level_select_events(
+    mut commands: Commands,
+    mut activated_events: EventReader<MenuActivated>,
+    levels: Res<LevelList>,
+    values: Res<MenuValues>,
+    slots: Res<SaveSlots>,
+    level_assets: Res<LevelAssets>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    mut active: ResMut<ActiveSave>,
+    mut level_selection: ResMut<LevelSelection>,
+    mut pending_placement: ResMut<PendingPlacement>,
+) {
+    for event in activated_events
+        .read()
+        .filter(|event| event.menu == "title" && event.entry == "level_start")
+    {
+        let Some(level) = levels.0.get(values.get_index("level")) else {
+            continue;
+        };
+        let slot = slots.latest();
+        let game = slot
+            .and_then(|slot| slots.slots[slot].clone())
+            .unwrap_or_default();
+        *active = ActiveSave::practice(slot, game);
+        *level_selection = LevelSelection::iid(level.iid.clone());
+        pending_placement.0 = ldtk_project_assets
+            .get(&level_assets.project)
+            .and_then(|project| entry_point(project, &level.iid))
+            .map(|position| position.extend(0.0));
+        commands.insert_resource(NextState(Some(AppState::Playing)));
+    }
+}
+
+#[derive(Component)]
+struct LevelThumbnail;
+
+fn spawn_thumbnail(mut commands: Commands) {
+    commands.spawn((
+        LevelThumbnail,
+        ImageBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                right: Val::Px(40.0),
+                bottom: Val::Px(40.0),
+                width: Val::Px(320.0),
+                height: Val::Px(180.0),
+                ..default()
+            },
+            visibility: Visibility::Hidden,
+            z_index: ZIndex::Global(1),
+            ..default()
+        },
+    ));
+}
+
+/// Shows the thumbnail of the selected level while the level select page is open.
+fn update_thumbnail(
+    menu_query: Query<&MenuRoot>,
+    levels: Res<LevelList>,
+    values: Res<MenuValues>,
+    asset_server: Res<AssetServer>,
+    mut thumbnail_query: Query<(&mut UiImage, &mut Visibility), With<LevelThumbnail>>,
+) {
+    let on_page = menu_query
+        .iter()
+        .any(|menu| menu.id == "title" && menu.page_title() == PAGE_TITLE);
+    let thumbnail = levels
+        .0
+        .get(values.get_index("level"))
+        .and_then(|level| level.thumbnail.clone())
+        .filter(|_| on_page);
+
+    for (mut image, mut visibility) in thumbnail_query.iter_mut() {
+        match &thumbnail {
+            Some(path) => {
+                let texture = asset_server.load(path.clone());
+                if image.texture != texture {
+                    image.texture = texture;
+                }
+                visibility.set_if_neq(Visibility::Inherited);
+            }
+            None => {
+                visibility.set_if_neq(Visibility::Hidden);
+            }
+        }
+    }
+}
+
+fn despawn_thumbnail(mut commands: Commands, query: Query<Entity, With<LevelThumbnail>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
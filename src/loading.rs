@@ -0,0 +1,135 @@
+use bevy::prelude::*;
+use bevy_asset_loader::prelude::*;
+
+use crate::asset_loader::FontAssets;
+use crate::localization::LocaleAssets;
+use crate::player_assets::PlayerAssets;
+use crate::state::AppState;
+use crate::world::LevelAssets;
+
+/// Assets whose progress is shown on the loading screen. The handles are looked
+/// up instead of loaded, so the settings used by the asset collections apply.
+const TRACKED_ASSETS: [&str; 3] = [
+    "tile-based-game.ldtk",
+    "textures/Fighter/player.png",
+    "animations/player.anim.ron",
+];
+
+pub struct LoadingPlugin;
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_loading_state(
+            LoadingState::new(AppState::Loading)
+                .continue_to_state(AppState::TitleScreen)
+                .load_collection::<PlayerAssets>()
+                .load_collection::<LevelAssets>()
+                .load_collection::<LocaleAssets>(),
+        )
+        .add_systems(OnEnter(AppState::Loading), spawn_loading_screen)
+        .add_systems(
+            Update,
+            update_loading_progress.run_if(in_state(AppState::Loading)),
+        )
+        .add_systems(OnExit(AppState::Loading), despawn_loading_screen);
+    }
+}
+
+#[derive(Component)]
+struct LoadingScreen;
+
+#[derive(Component)]
+struct LoadingBar;
+
+#[derive(Component)]
+struct LoadingText;
+
+fn spawn_loading_screen(mut commands: Commands, font_assets: Res<FontAssets>) {
+    commands
+        .spawn((
+            LoadingScreen,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: Color::BLACK.into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                LoadingText,
+                TextBundle::from_section(
+                    "Loading 0%",
+                    TextStyle {
+                        font: font_assets.menu_font.clone(),
+                        font_size: 50.0,
+                        color: Color::WHITE,
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::all(Val::Px(15.0)),
+                    ..default()
+                }),
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(400.0),
+                        height: Val::Px(20.0),
+                        ..default()
+                    },
+                    background_color: Color::rgba(1.0, 1.0, 1.0, 0.2).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        LoadingBar,
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Percent(0.0),
+                                height: Val::Percent(100.0),
+                                ..default()
+                            },
+                            background_color: Color::WHITE.into(),
+                            ..default()
+                        },
+                    ));
+                });
+        });
+}
+
+fn update_loading_progress(
+    asset_server: Res<AssetServer>,
+    mut bar_query: Query<&mut Style, With<LoadingBar>>,
+    mut text_query: Query<&mut Text, With<LoadingText>>,
+) {
+    let loaded = TRACKED_ASSETS
+        .iter()
+        .filter(|path| {
+            asset_server
+                .get_handle_untyped(**path)
+                .map(|handle| asset_server.is_loaded_with_dependencies(handle.id()))
+                .unwrap_or(false)
+        })
+        .count();
+    let progress = loaded as f32 / TRACKED_ASSETS.len() as f32 * 100.0;
+
+    for mut style in bar_query.iter_mut() {
+        style.width = Val::Percent(progress);
+    }
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = format!("Loading {:.0}%", progress);
+    }
+}
+
+fn despawn_loading_screen(mut commands: Commands, query: Query<Entity, With<LoadingScreen>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
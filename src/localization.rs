@@ -0,0 +1,143 @@
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::prelude::*;
+use bevy::reflect::TypePath;
+use bevy::ui::UiSystem;
+use bevy::utils::BoxedFuture;
+use bevy_asset_loader::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use thiserror::Error;
+
+use crate::settings::Settings;
+use crate::state::AppState;
+
+/// Used for keys missing in the selected language.
+pub const FALLBACK_LANGUAGE: &str = "en";
+
+pub struct LocalizationPlugin;
+
+impl Plugin for LocalizationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Locale>()
+            .init_asset_loader::<LocaleLoader>()
+            .init_resource::<Localization>()
+            .add_systems(OnExit(AppState::Loading), collect_locales)
+            .add_systems(
+                PostUpdate,
+                (select_language, update_localized_texts)
+                    .chain()
+                    .before(UiSystem::Layout),
+            );
+    }
+}
+
+/// Strings of one language, loaded from `assets/locales/<language>.locale.ron`.
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct Locale {
+    pub language: String,
+    pub strings: HashMap<String, String>,
+}
+
+/// Loaded by `loading::LoadingPlugin` before the title screen is shown.
+#[derive(AssetCollection, Resource)]
+pub struct LocaleAssets {
+    #[asset(
+        paths("locales/en.locale.ron", "locales/de.locale.ron"),
+        collection(typed)
+    )]
+    pub locales: Vec<Handle<Locale>>,
+}
+
+/// All loaded locales and the selected language.
+#[derive(Resource, Debug, Default)]
+pub struct Localization {
+    language: String,
+    locales: HashMap<String, HashMap<String, String>>,
+}
+
+impl Localization {
+    /// Looks up `key` in the selected language, then in English. Unknown keys
+    /// are returned as they are, so plain text can be passed through as well.
+    pub fn get(&self, key: &str) -> String {
+        [self.language.as_str(), FALLBACK_LANGUAGE]
+            .iter()
+            .find_map(|language| self.locales.get(*language)?.get(key))
+            .cloned()
+            .unwrap_or_else(|| key.to_string())
+    }
+}
+
+/// Text whose first section shows the translation of the key, updated when the
+/// language changes.
+#[derive(Component, Debug, Clone)]
+pub struct LocalizedText(pub String);
+
+fn collect_locales(
+    mut localization: ResMut<Localization>,
+    locale_assets: Res<LocaleAssets>,
+    locales: Res<Assets<Locale>>,
+) {
+    for handle in &locale_assets.locales {
+        if let Some(locale) = locales.get(handle) {
+            localization
+                .locales
+                .insert(locale.language.clone(), locale.strings.clone());
+        }
+    }
+}
+
+fn select_language(settings: Res<Settings>, mut localization: ResMut<Localization>) {
+    if localization.language != settings.language {
+        localization.language = settings.language.clone();
+    }
+}
+
+fn update_localized_texts(
+    localization: Res<Localization>,
+    mut text_query: Query<(Ref<LocalizedText>, &mut Text)>,
+) {
+    for (localized, mut text) in text_query.iter_mut() {
+        if !localization.is_changed() && !localized.is_changed() {
+            continue;
+        }
+        let value = localization.get(&localized.0);
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct LocaleLoader;
+
+#[derive(Debug, Error)]
+pub enum LocaleLoaderError {
+    #[error("Could not read locale: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse locale: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+impl AssetLoader for LocaleLoader {
+    type Asset = Locale;
+    type Settings = ();
+    type Error = LocaleLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Locale, LocaleLoaderError>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            Ok(ron::de::from_bytes::<Locale>(&bytes)?)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["locale.ron"]
+    }
+}
@@ -1,32 +1,53 @@
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 
+mod animation;
 mod asset_loader;
+mod background;
+mod bindings;
 mod camera;
+mod controls;
+mod coop;
+mod game_over;
+mod ghost;
 mod input;
+mod juice;
+mod level_select;
+mod loading;
+mod localization;
 mod menu;
+mod menu_builder;
+mod particles;
 mod player;
 mod player_assets;
+mod replay;
+mod save;
+mod settings;
+mod speedrun;
 mod state;
+mod title;
 mod world;
 
 fn main() {
+    // Needed before the window is created, the error is logged by the plugin.
+    let (settings, load_error) = match settings::Settings::load() {
+        Ok(settings) => (settings, None),
+        Err(error) => (settings::Settings::default(), Some(error)),
+    };
+
     App::new()
         .add_state::<state::AppState>()
         .add_plugins((
             DefaultPlugins
                 .set(ImagePlugin::default_nearest())
                 .set(WindowPlugin {
-                    primary_window: Some(Window {
-                        title: "Platformer".into(),
-                        resolution: (1200., 750.).into(),
-                        ..default()
-                    }),
+                    primary_window: Some(settings.window()),
                     ..default()
                 }),
             RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0).with_default_system_setup(false),
             // RapierDebugRenderPlugin::default(),
             asset_loader::AssetLoaderPlugin,
+            animation::AnimationPlugin,
             world::WorldPlugin,
             camera::CameraPlugin,
             player::PlayerPlugin,
@@ -34,8 +55,32 @@ fn main() {
             menu::MenuPlugin,
             input::InputPlugin,
             player_assets::AssetLoadingPlugin,
+            background::BackgroundPlugin,
+            particles::ParticlePlugin,
+            juice::JuicePlugin,
+        ))
+        .add_plugins((
+            loading::LoadingPlugin,
+            title::TitlePlugin,
+            game_over::GameOverPlugin,
+            menu_builder::MenuBuilderPlugin,
+            settings::SettingsPlugin { load_error },
+            localization::LocalizationPlugin,
+            bindings::BindingsPlugin,
+            controls::ControlsPlugin,
+            coop::CoopPlugin,
+            replay::ReplayPlugin,
+            ghost::GhostPlugin,
+            speedrun::SpeedrunPlugin,
+            save::SavePlugin,
+            level_select::LevelSelectPlugin,
         ))
+        .insert_resource(settings)
         .insert_resource(RapierConfiguration {
+            timestep_mode: TimestepMode::Fixed {
+                dt: (1.0 / state::SIMULATION_HZ) as f32,
+                substeps: 1,
+            },
             ..Default::default()
         })
         .run();
@@ -0,0 +1,791 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+use crate::asset_loader::FontAssets;
+use crate::input::MenuInputEvent;
+use crate::localization::{Localization, LocalizedText};
+use crate::state::ScheduleSet;
+
+/// Navigation, activation and UI of every `MenuRoot`. Systems reacting to menu
+/// events should run after this set.
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct MenuSet;
+
+pub struct MenuBuilderPlugin;
+
+impl Plugin for MenuBuilderPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MenuValues>()
+            .add_event::<MenuActivated>()
+            .add_event::<MenuValueChanged>()
+            .add_event::<MenuClosed>()
+            .configure_sets(Update, MenuSet.after(ScheduleSet::MenuInput))
+            .add_systems(
+                Update,
+                (
+                    navigate_menus,
+                    hover_menu_items,
+                    press_menu_items,
+                    build_menu_panels,
+                    (update_menu_labels, highlight_focused_items),
+                )
+                    .chain()
+                    .in_set(MenuSet),
+            );
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum MenuEntryKind {
+    Button,
+    /// On/off option backed by a bool in `MenuValues`.
+    Toggle,
+    /// Number in `min..=max` backed by a float in `MenuValues`.
+    Slider {
+        min: f32,
+        max: f32,
+        step: f32,
+    },
+    /// One of `options`, backed by an index in `MenuValues`.
+    Choice(Vec<String>),
+    Submenu(MenuPage),
+    Back,
+}
+
+#[derive(Clone, Debug)]
+pub struct MenuEntry {
+    pub id: &'static str,
+    pub label: String,
+    pub kind: MenuEntryKind,
+    pub enabled: bool,
+}
+
+/// A list of entries shown together, declared with the builder methods:
+///
+/// `MenuPage::new("menu.pause.title").button("resume", "menu.resume").back("menu.back")`
+#[derive(Clone, Debug, Default)]
+pub struct MenuPage {
+    pub title: String,
+    pub entries: Vec<MenuEntry>,
+}
+
+impl MenuPage {
+    pub fn new(title: impl Into<String>) -> Self {
+        MenuPage {
+            title: title.into(),
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn button(self, id: &'static str, label: impl Into<String>) -> Self {
+        self.with(id, label, MenuEntryKind::Button)
+    }
+
+    pub fn toggle(self, id: &'static str, label: impl Into<String>) -> Self {
+        self.with(id, label, MenuEntryKind::Toggle)
+    }
+
+    pub fn slider(
+        self,
+        id: &'static str,
+        label: impl Into<String>,
+        min: f32,
+        max: f32,
+        step: f32,
+    ) -> Self {
+        self.with(id, label, MenuEntryKind::Slider { min, max, step })
+    }
+
+    pub fn choice(
+        self,
+        id: &'static str,
+        label: impl Into<String>,
+        options: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        let options = options.into_iter().map(Into::into).collect();
+        self.with(id, label, MenuEntryKind::Choice(options))
+    }
+
+    pub fn submenu(self, id: &'static str, label: impl Into<String>, page: MenuPage) -> Self {
+        self.with(id, label, MenuEntryKind::Submenu(page))
+    }
+
+    pub fn back(self, label: impl Into<String>) -> Self {
+        self.with("back", label, MenuEntryKind::Back)
+    }
+
+    /// Greys out the entry added last, it is skipped by focus navigation.
+    pub fn disabled(mut self) -> Self {
+        if let Some(entry) = self.entries.last_mut() {
+            entry.enabled = false;
+        }
+        self
+    }
+
+    fn with(mut self, id: &'static str, label: impl Into<String>, kind: MenuEntryKind) -> Self {
+        self.entries.push(MenuEntry {
+            id,
+            label: label.into(),
+            kind,
+            enabled: true,
+        });
+        self
+    }
+
+    fn first_enabled(&self) -> usize {
+        self.entries
+            .iter()
+            .position(|entry| entry.enabled)
+            .unwrap_or(0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MenuValue {
+    Bool(bool),
+    Float(f32),
+    Index(usize),
+}
+
+/// Current values of toggles and sliders by entry id. Owners of a menu write the
+/// real state in here, the menu only displays and edits it. Buttons can show a
+/// text next to their label and be highlighted to draw attention to them.
+#[derive(Resource, Debug, Default)]
+pub struct MenuValues {
+    values: HashMap<&'static str, MenuValue>,
+    texts: HashMap<&'static str, String>,
+    highlighted: HashSet<&'static str>,
+}
+
+impl MenuValues {
+    pub fn set_bool(&mut self, id: &'static str, value: bool) {
+        self.values.insert(id, MenuValue::Bool(value));
+    }
+
+    pub fn set_float(&mut self, id: &'static str, value: f32) {
+        self.values.insert(id, MenuValue::Float(value));
+    }
+
+    pub fn set_index(&mut self, id: &'static str, value: usize) {
+        self.values.insert(id, MenuValue::Index(value));
+    }
+
+    pub fn set_text(&mut self, id: &'static str, text: String) {
+        self.texts.insert(id, text);
+    }
+
+    pub fn set_highlighted(&mut self, id: &'static str, highlighted: bool) {
+        if highlighted {
+            self.highlighted.insert(id);
+        } else {
+            self.highlighted.remove(id);
+        }
+    }
+
+    pub fn get_bool(&self, id: &str) -> bool {
+        matches!(self.values.get(id), Some(MenuValue::Bool(true)))
+    }
+
+    pub fn get_float(&self, id: &str) -> Option<f32> {
+        match self.values.get(id) {
+            Some(MenuValue::Float(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_index(&self, id: &str) -> usize {
+        match self.values.get(id) {
+            Some(MenuValue::Index(value)) => *value,
+            _ => 0,
+        }
+    }
+}
+
+/// A button entry was activated.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct MenuActivated {
+    pub menu: &'static str,
+    pub entry: &'static str,
+}
+
+/// A toggle or slider entry was changed, `MenuValues` already holds the new value.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct MenuValueChanged {
+    pub menu: &'static str,
+    pub entry: &'static str,
+    pub value: MenuValue,
+}
+
+/// Back was requested on the first page of a menu.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct MenuClosed {
+    pub menu: &'static str,
+}
+
+/// Spawn together with a `NodeBundle` to get a navigable menu. The entries are
+/// spawned as a panel child, other children of the node are left alone.
+#[derive(Component, Debug)]
+pub struct MenuRoot {
+    pub id: &'static str,
+    pages: Vec<MenuPage>,
+    focus: usize,
+    dirty: bool,
+}
+
+impl MenuRoot {
+    pub fn new(id: &'static str, page: MenuPage) -> Self {
+        MenuRoot {
+            id,
+            focus: page.first_enabled(),
+            pages: vec![page],
+            dirty: true,
+        }
+    }
+
+    fn page(&self) -> &MenuPage {
+        self.pages.last().expect("Menu always has a page")
+    }
+
+    /// Title of the page currently shown.
+    pub fn page_title(&self) -> &str {
+        &self.page().title
+    }
+
+    fn entry(&self) -> Option<&MenuEntry> {
+        self.page().entries.get(self.focus)
+    }
+
+    fn focus(&mut self, index: usize) {
+        if self
+            .page()
+            .entries
+            .get(index)
+            .is_some_and(|entry| entry.enabled)
+        {
+            self.focus = index;
+        }
+    }
+
+    fn move_focus(&mut self, forward: bool) {
+        let len = self.page().entries.len();
+        let next = (1..=len)
+            .map(|offset| {
+                if forward {
+                    (self.focus + offset) % len
+                } else {
+                    (self.focus + len - offset) % len
+                }
+            })
+            .find(|index| self.page().entries[*index].enabled);
+        if let Some(index) = next {
+            self.focus = index;
+        }
+    }
+
+    fn push(&mut self, page: MenuPage) {
+        self.focus = page.first_enabled();
+        self.pages.push(page);
+        self.dirty = true;
+    }
+
+    /// Returns false if already on the first page.
+    fn pop(&mut self) -> bool {
+        if self.pages.len() <= 1 {
+            return false;
+        }
+        self.pages.pop();
+        self.focus = self.page().first_enabled();
+        self.dirty = true;
+        true
+    }
+}
+
+#[derive(Component)]
+struct MenuPanel;
+
+#[derive(Component)]
+struct MenuEntryButton {
+    root: Entity,
+    index: usize,
+}
+
+#[derive(Component)]
+struct MenuEntryLabel {
+    root: Entity,
+    index: usize,
+}
+
+#[derive(SystemParam)]
+struct MenuWriters<'w> {
+    values: ResMut<'w, MenuValues>,
+    activated: EventWriter<'w, MenuActivated>,
+    changed: EventWriter<'w, MenuValueChanged>,
+    closed: EventWriter<'w, MenuClosed>,
+}
+
+enum MenuAction {
+    Activate,
+    Adjust(f32),
+    Back,
+}
+
+fn apply_action(menu: &mut MenuRoot, action: MenuAction, writers: &mut MenuWriters) {
+    if let MenuAction::Back = action {
+        if !menu.pop() {
+            writers.closed.send(MenuClosed { menu: menu.id });
+        }
+        return;
+    }
+    let Some(entry) = menu.entry().filter(|entry| entry.enabled).cloned() else {
+        return;
+    };
+
+    let value = match (&entry.kind, action) {
+        (MenuEntryKind::Button, MenuAction::Activate) => {
+            writers.activated.send(MenuActivated {
+                menu: menu.id,
+                entry: entry.id,
+            });
+            return;
+        }
+        (MenuEntryKind::Submenu(page), MenuAction::Activate) => {
+            menu.push(page.clone());
+            return;
+        }
+        (MenuEntryKind::Back, MenuAction::Activate) => {
+            apply_action(menu, MenuAction::Back, writers);
+            return;
+        }
+        (MenuEntryKind::Toggle, _) => MenuValue::Bool(!writers.values.get_bool(entry.id)),
+        (MenuEntryKind::Slider { min, max, step }, action) => {
+            let current = writers.values.get_float(entry.id).unwrap_or(*min);
+            let value = match action {
+                // Activating steps up and wraps around, so sliders work with a mouse.
+                MenuAction::Activate if current + step > max + step * 0.5 => *min,
+                MenuAction::Activate => current + step,
+                MenuAction::Adjust(direction) => current + step * direction,
+                MenuAction::Back => current,
+            };
+            MenuValue::Float(value.clamp(*min, *max))
+        }
+        (MenuEntryKind::Choice(options), action) if !options.is_empty() => {
+            let current = writers.values.get_index(entry.id);
+            let value = match action {
+                MenuAction::Adjust(direction) if direction < 0.0 => current + options.len() - 1,
+                _ => current + 1,
+            };
+            MenuValue::Index(value % options.len())
+        }
+        _ => return,
+    };
+
+    writers.values.values.insert(entry.id, value);
+    writers.changed.send(MenuValueChanged {
+        menu: menu.id,
+        entry: entry.id,
+        value,
+    });
+}
+
+fn navigate_menus(
+    mut menu_events: EventReader<MenuInputEvent>,
+    mut menu_query: Query<&mut MenuRoot>,
+    mut writers: MenuWriters,
+) {
+    for event in menu_events.read() {
+        for mut menu in menu_query.iter_mut() {
+            match event {
+                MenuInputEvent::Up => menu.move_focus(false),
+                MenuInputEvent::Down => menu.move_focus(true),
+                MenuInputEvent::Left => {
+                    apply_action(&mut menu, MenuAction::Adjust(-1.0), &mut writers)
+                }
+                MenuInputEvent::Right => {
+                    apply_action(&mut menu, MenuAction::Adjust(1.0), &mut writers)
+                }
+                MenuInputEvent::Select => {
+                    apply_action(&mut menu, MenuAction::Activate, &mut writers)
+                }
+                MenuInputEvent::CloseMenu => {
+                    apply_action(&mut menu, MenuAction::Back, &mut writers)
+                }
+                MenuInputEvent::StepFrame => {}
+            }
+        }
+    }
+}
+
+fn hover_menu_items(
+    interaction_query: Query<(&Interaction, &MenuEntryButton), Changed<Interaction>>,
+    mut menu_query: Query<&mut MenuRoot>,
+) {
+    for (interaction, button) in interaction_query.iter() {
+        if *interaction == Interaction::Hovered {
+            if let Ok(mut menu) = menu_query.get_mut(button.root) {
+                menu.focus(button.index);
+            }
+        }
+    }
+}
+
+fn press_menu_items(
+    interaction_query: Query<(&Interaction, &MenuEntryButton), Changed<Interaction>>,
+    mut menu_query: Query<&mut MenuRoot>,
+    mut writers: MenuWriters,
+) {
+    for (interaction, button) in interaction_query.iter() {
+        if *interaction == Interaction::Pressed {
+            if let Ok(mut menu) = menu_query.get_mut(button.root) {
+                menu.focus(button.index);
+                apply_action(&mut menu, MenuAction::Activate, &mut writers);
+            }
+        }
+    }
+}
+
+/// Spawns the entries of the current page whenever a menu is created or its page changes.
+fn build_menu_panels(
+    mut commands: Commands,
+    mut menu_query: Query<(Entity, &mut MenuRoot), Changed<MenuRoot>>,
+    panel_query: Query<(Entity, &Parent), With<MenuPanel>>,
+    font_assets: Res<FontAssets>,
+    values: Res<MenuValues>,
+    localization: Res<Localization>,
+) {
+    for (root, mut menu) in menu_query.iter_mut() {
+        if !menu.dirty {
+            continue;
+        }
+        menu.dirty = false;
+
+        for (panel, parent) in panel_query.iter() {
+            if parent.get() == root {
+                commands.entity(panel).despawn_recursive();
+            }
+        }
+
+        let page = menu.page();
+        commands.entity(root).with_children(|parent| {
+            parent
+                .spawn((
+                    MenuPanel,
+                    NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+                        ..default()
+                    },
+                ))
+                .with_children(|parent| {
+                    if !page.title.is_empty() {
+                        parent.spawn((
+                            LocalizedText(page.title.clone()),
+                            TextBundle::from_section(
+                                localization.get(&page.title),
+                                TextStyle {
+                                    font: font_assets.menu_font.clone(),
+                                    font_size: 50.0,
+                                    color: Color::WHITE,
+                                },
+                            )
+                            .with_style(Style {
+                                margin: UiRect::all(Val::Px(15.0)),
+                                ..default()
+                            }),
+                        ));
+                    }
+
+                    for (index, entry) in page.entries.iter().enumerate() {
+                        parent
+                            .spawn((
+                                MenuEntryButton { root, index },
+                                ButtonBundle {
+                                    style: Style {
+                                        margin: UiRect::all(Val::Px(10.0)),
+                                        ..default()
+                                    },
+                                    background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+                                    ..default()
+                                },
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((
+                                    MenuEntryLabel { root, index },
+                                    TextBundle::from_section(
+                                        entry_text(entry, &values, &localization),
+                                        TextStyle {
+                                            font: font_assets.menu_font.clone(),
+                                            font_size: 25.0,
+                                            color: entry_color(entry, &values),
+                                        },
+                                    )
+                                    .with_style(Style {
+                                        margin: UiRect::all(Val::Px(15.0)),
+                                        ..default()
+                                    }),
+                                ));
+                            });
+                    }
+                });
+        });
+    }
+}
+
+/// Labels, page titles and choice options are localization keys.
+fn entry_text(entry: &MenuEntry, values: &MenuValues, localization: &Localization) -> String {
+    let label = localization.get(&entry.label);
+    match &entry.kind {
+        MenuEntryKind::Toggle => {
+            let state = if values.get_bool(entry.id) {
+                "menu.on"
+            } else {
+                "menu.off"
+            };
+            format!("{}: {}", label, localization.get(state))
+        }
+        MenuEntryKind::Slider { min, step, .. } => {
+            let value = values.get_float(entry.id).unwrap_or(*min);
+            let precision = if *step >= 1.0 { 0 } else { 1 };
+            format!("{}: < {:.*} >", label, precision, value)
+        }
+        MenuEntryKind::Choice(options) => {
+            let option = options
+                .get(values.get_index(entry.id))
+                .map(|option| localization.get(option))
+                .unwrap_or_default();
+            format!("{}: < {} >", label, option)
+        }
+        _ => match values.texts.get(entry.id) {
+            Some(text) => format!("{}: {}", label, localization.get(text)),
+            None => label,
+        },
+    }
+}
+
+fn entry_color(entry: &MenuEntry, values: &MenuValues) -> Color {
+    if !entry.enabled {
+        Color::GRAY
+    } else if values.highlighted.contains(entry.id) {
+        Color::RED
+    } else {
+        Color::WHITE
+    }
+}
+
+fn update_menu_labels(
+    mut label_query: Query<(&mut Text, &MenuEntryLabel)>,
+    menu_query: Query<&MenuRoot>,
+    values: Res<MenuValues>,
+    localization: Res<Localization>,
+) {
+    for (mut text, label) in label_query.iter_mut() {
+        let Ok(menu) = menu_query.get(label.root) else {
+            continue;
+        };
+        let Some(entry) = menu.page().entries.get(label.index) else {
+            continue;
+        };
+        let value = entry_text(entry, &values, &localization);
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+        let color = entry_color(entry, &values);
+        if text.sections[0].style.color != color {
+            text.sections[0].style.color = color;
+        }
+    }
+}
+
+fn highlight_focused_items(
+    mut button_query: Query<(&mut BackgroundColor, &MenuEntryButton)>,
+    menu_query: Query<&MenuRoot>,
+) {
+    for (mut background_color, button) in button_query.iter_mut() {
+        let focused = menu_query
+            .get(button.root)
+            .is_ok_and(|menu| menu.focus == button.index);
+        background_color.0 = if focused {
+            Color::rgba(0.0, 0.0, 0.0, 0.8)
+        } else {
+            Color::rgba(0.0, 0.0, 0.0, 0.5)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::event::Events;
+    use bevy::ecs::system::SystemState;
+
+    fn options_page() -> MenuPage {
+        MenuPage::new("options")
+            .slider("volume", "Volume", 0.0, 1.0, 0.25)
+            .choice("size", "Size", ["small", "large"])
+            .back("Back")
+    }
+
+    fn page() -> MenuPage {
+        MenuPage::new("title")
+            .button("first", "First")
+            .disabled()
+            .button("second", "Second")
+            .button("third", "Third")
+            .disabled()
+            .submenu("options", "Options", options_page())
+    }
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.init_resource::<MenuValues>();
+        world.init_resource::<Events<MenuActivated>>();
+        world.init_resource::<Events<MenuValueChanged>>();
+        world.init_resource::<Events<MenuClosed>>();
+        world
+    }
+
+    fn apply(world: &mut World, menu: &mut MenuRoot, action: MenuAction) {
+        let mut state = SystemState::<MenuWriters>::new(world);
+        apply_action(menu, action, &mut state.get_mut(world));
+        state.apply(world);
+    }
+
+    fn focused(menu: &MenuRoot) -> &'static str {
+        menu.entry().expect("focus is on an entry").id
+    }
+
+    #[test]
+    fn focus_starts_on_the_first_enabled_entry() {
+        assert_eq!(focused(&MenuRoot::new("menu", page())), "second");
+    }
+
+    #[test]
+    fn moving_focus_skips_disabled_entries_and_wraps() {
+        let mut menu = MenuRoot::new("menu", page());
+        menu.move_focus(true);
+        assert_eq!(focused(&menu), "options");
+        menu.move_focus(true);
+        assert_eq!(focused(&menu), "second");
+        menu.move_focus(false);
+        assert_eq!(focused(&menu), "options");
+        menu.move_focus(false);
+        assert_eq!(focused(&menu), "second");
+    }
+
+    #[test]
+    fn moving_focus_without_enabled_entries_keeps_it() {
+        let mut menu = MenuRoot::new("menu", MenuPage::new("empty").button("a", "A").disabled());
+        menu.move_focus(true);
+        assert_eq!(menu.focus, 0);
+    }
+
+    #[test]
+    fn disabled_entries_can_not_be_focused() {
+        let mut menu = MenuRoot::new("menu", page());
+        menu.focus(0);
+        assert_eq!(focused(&menu), "second");
+        menu.focus(10);
+        assert_eq!(focused(&menu), "second");
+        menu.focus(3);
+        assert_eq!(focused(&menu), "options");
+    }
+
+    #[test]
+    fn submenus_are_pushed_and_popped() {
+        let mut world = world();
+        let mut menu = MenuRoot::new("menu", page());
+        menu.focus(3);
+        apply(&mut world, &mut menu, MenuAction::Activate);
+        assert_eq!(menu.page_title(), "options");
+        assert_eq!(focused(&menu), "volume");
+
+        menu.focus(2);
+        apply(&mut world, &mut menu, MenuAction::Activate);
+        assert_eq!(menu.page_title(), "title");
+        assert_eq!(focused(&menu), "second");
+        assert!(world.resource::<Events<MenuClosed>>().is_empty());
+
+        apply(&mut world, &mut menu, MenuAction::Back);
+        assert_eq!(world.resource::<Events<MenuClosed>>().len(), 1);
+    }
+
+    #[test]
+    fn buttons_send_activated_events() {
+        let mut world = world();
+        let mut menu = MenuRoot::new("menu", page());
+        apply(&mut world, &mut menu, MenuAction::Activate);
+        let events = world.resource::<Events<MenuActivated>>();
+        let mut reader = events.get_reader();
+        let activated: Vec<_> = reader.read(events).map(|event| event.entry).collect();
+        assert_eq!(activated, ["second"]);
+    }
+
+    #[test]
+    fn sliders_step_clamp_and_wrap() {
+        let mut world = world();
+        let mut menu = MenuRoot::new("menu", page());
+        menu.push(options_page());
+
+        apply(&mut world, &mut menu, MenuAction::Adjust(-1.0));
+        assert_eq!(
+            world.resource::<MenuValues>().get_float("volume"),
+            Some(0.0)
+        );
+        for _ in 0..4 {
+            apply(&mut world, &mut menu, MenuAction::Adjust(1.0));
+        }
+        apply(&mut world, &mut menu, MenuAction::Adjust(1.0));
+        assert_eq!(
+            world.resource::<MenuValues>().get_float("volume"),
+            Some(1.0)
+        );
+        apply(&mut world, &mut menu, MenuAction::Activate);
+        assert_eq!(
+            world.resource::<MenuValues>().get_float("volume"),
+            Some(0.0)
+        );
+        apply(&mut world, &mut menu, MenuAction::Activate);
+        assert_eq!(
+            world.resource::<MenuValues>().get_float("volume"),
+            Some(0.25)
+        );
+    }
+
+    #[test]
+    fn choices_cycle_in_both_directions() {
+        let mut world = world();
+        let mut menu = MenuRoot::new("menu", page());
+        menu.push(options_page());
+        menu.focus(1);
+
+        apply(&mut world, &mut menu, MenuAction::Adjust(-1.0));
+        assert_eq!(world.resource::<MenuValues>().get_index("size"), 1);
+        apply(&mut world, &mut menu, MenuAction::Adjust(1.0));
+        assert_eq!(world.resource::<MenuValues>().get_index("size"), 0);
+        apply(&mut world, &mut menu, MenuAction::Activate);
+        assert_eq!(world.resource::<MenuValues>().get_index("size"), 1);
+    }
+
+    #[test]
+    fn values_default_when_unset_or_of_another_kind() {
+        let mut values = MenuValues::default();
+        assert!(!values.get_bool("toggle"));
+        assert_eq!(values.get_float("slider"), None);
+        assert_eq!(values.get_index("choice"), 0);
+
+        values.set_bool("toggle", true);
+        values.set_float("slider", 0.5);
+        values.set_index("choice", 2);
+        assert!(values.get_bool("toggle"));
+        assert_eq!(values.get_float("slider"), Some(0.5));
+        assert_eq!(values.get_index("choice"), 2);
+
+        values.set_float("toggle", 1.0);
+        assert!(!values.get_bool("toggle"));
+        assert_eq!(values.get_index("slider"), 0);
+    }
+}
@@ -0,0 +1,412 @@
+use bevy::prelude::*;
+use std::f32::consts::PI;
+
+use crate::animation::{AnimationSet, Animator};
+use crate::player::{PlayerJumped, PlayerLanded, PLAYER_FEET_OFFSET};
+use crate::player_assets::PlayerSprite;
+use crate::replay::SimulationSeed;
+use crate::state::ScheduleSet;
+
+pub struct ParticlePlugin;
+
+impl Plugin for ParticlePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ParticleRng>()
+            .add_event::<SpawnParticles>()
+            .add_systems(
+                Update,
+                (
+                    seed_particles.run_if(resource_changed::<SimulationSeed>()),
+                    (
+                        landing_dust,
+                        jump_puffs,
+                        attach_run_dust_emitters,
+                        run_dust.after(AnimationSet),
+                    ),
+                    (update_emitters, spawn_particle_bursts).chain(),
+                    update_particles,
+                )
+                    .chain()
+                    .in_set(ScheduleSet::PostTransformUpdate),
+            );
+    }
+}
+
+/// Frames of a texture atlas played over the lifetime of a particle.
+#[derive(Clone, Debug)]
+pub struct ParticleFrames {
+    pub atlas: Handle<TextureAtlas>,
+    pub first: usize,
+    pub last: usize,
+}
+
+/// Describes how the particles of an effect are spawned and how they behave.
+#[derive(Clone, Debug)]
+pub struct ParticleEffect {
+    pub count: u32,
+    pub lifetime: f32,
+    pub speed: (f32, f32),
+    /// Direction of the particles in radians, randomized by `spread` in both directions.
+    pub angle: f32,
+    pub spread: f32,
+    pub gravity: f32,
+    pub size: f32,
+    pub start_color: Color,
+    pub end_color: Color,
+    pub frames: Option<ParticleFrames>,
+}
+
+impl ParticleEffect {
+    pub fn landing_dust() -> Self {
+        ParticleEffect {
+            count: 10,
+            lifetime: 0.45,
+            speed: (20.0, 60.0),
+            angle: PI / 2.0,
+            spread: PI / 2.2,
+            gravity: -60.0,
+            size: 3.0,
+            start_color: Color::rgba(0.8, 0.75, 0.65, 0.9),
+            end_color: Color::rgba(0.8, 0.75, 0.65, 0.0),
+            frames: None,
+        }
+    }
+
+    pub fn run_dust() -> Self {
+        ParticleEffect {
+            count: 3,
+            lifetime: 0.3,
+            speed: (10.0, 30.0),
+            angle: PI / 2.0,
+            spread: PI / 3.0,
+            gravity: -30.0,
+            size: 2.0,
+            ..ParticleEffect::landing_dust()
+        }
+    }
+
+    pub fn jump_puff() -> Self {
+        ParticleEffect {
+            count: 8,
+            lifetime: 0.35,
+            speed: (25.0, 50.0),
+            angle: -PI / 2.0,
+            spread: PI / 2.5,
+            gravity: 0.0,
+            size: 3.0,
+            start_color: Color::rgba(1.0, 1.0, 1.0, 0.8),
+            end_color: Color::rgba(1.0, 1.0, 1.0, 0.0),
+            frames: None,
+        }
+    }
+
+    pub fn hit() -> Self {
+        ParticleEffect {
+            count: 12,
+            lifetime: 0.25,
+            speed: (80.0, 160.0),
+            angle: 0.0,
+            spread: PI,
+            gravity: -200.0,
+            size: 2.0,
+            start_color: Color::rgb(1.0, 0.9, 0.5),
+            end_color: Color::rgba(1.0, 0.2, 0.1, 0.0),
+            frames: None,
+        }
+    }
+}
+
+/// One-shot burst of `effect.count` particles at `position`.
+#[derive(Event, Clone, Debug)]
+pub struct SpawnParticles {
+    pub position: Vec3,
+    pub effect: ParticleEffect,
+}
+
+/// Continuously spawns particles at the position of its entity. The emitter
+/// despawns itself once its optional lifetime runs out.
+#[derive(Component, Clone, Debug)]
+pub struct ParticleEmitter {
+    pub effect: ParticleEffect,
+    /// Particles per second, `effect.count` is ignored for emitters.
+    pub rate: f32,
+    pub active: bool,
+    pub lifetime: Option<Timer>,
+    accumulator: f32,
+}
+
+impl ParticleEmitter {
+    pub fn new(effect: ParticleEffect, rate: f32) -> Self {
+        ParticleEmitter {
+            effect,
+            rate,
+            active: true,
+            lifetime: None,
+            accumulator: 0.0,
+        }
+    }
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct Particle {
+    velocity: Vec2,
+    age: f32,
+    lifetime: f32,
+    gravity: f32,
+    start_color: Color,
+    end_color: Color,
+    frames: Option<(usize, usize)>,
+}
+
+/// Xorshift generator, particles only need cheap randomness.
+#[derive(Resource, Debug)]
+pub struct ParticleRng(u64);
+
+impl Default for ParticleRng {
+    fn default() -> Self {
+        ParticleRng(0x9e37_79b9_7f4a_7c15)
+    }
+}
+
+impl ParticleRng {
+    /// Xorshift never leaves a zero state, that seed uses the default.
+    pub fn new(seed: u64) -> Self {
+        match seed {
+            0 => ParticleRng::default(),
+            seed => ParticleRng(seed),
+        }
+    }
+
+    pub fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+}
+
+fn seed_particles(seed: Res<SimulationSeed>, mut rng: ResMut<ParticleRng>) {
+    *rng = ParticleRng::new(seed.0);
+}
+
+fn spawn_particle(
+    commands: &mut Commands,
+    rng: &mut ParticleRng,
+    position: Vec3,
+    effect: &ParticleEffect,
+) {
+    let angle = effect.angle + rng.range(-effect.spread, effect.spread);
+    let speed = rng.range(effect.speed.0, effect.speed.1);
+    let particle = Particle {
+        velocity: Vec2::from_angle(angle) * speed,
+        age: 0.0,
+        lifetime: effect.lifetime * rng.range(0.7, 1.0),
+        gravity: effect.gravity,
+        start_color: effect.start_color,
+        end_color: effect.end_color,
+        frames: effect
+            .frames
+            .as_ref()
+            .map(|frames| (frames.first, frames.last)),
+    };
+    let transform = Transform::from_translation(position);
+    match &effect.frames {
+        Some(frames) => {
+            commands.spawn((
+                particle,
+                SpriteSheetBundle {
+                    sprite: TextureAtlasSprite {
+                        index: frames.first,
+                        color: effect.start_color,
+                        custom_size: Some(Vec2::splat(effect.size)),
+                        ..default()
+                    },
+                    texture_atlas: frames.atlas.clone(),
+                    transform,
+                    ..default()
+                },
+            ));
+        }
+        None => {
+            commands.spawn((
+                particle,
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: effect.start_color,
+                        custom_size: Some(Vec2::splat(effect.size)),
+                        ..default()
+                    },
+                    transform,
+                    ..default()
+                },
+            ));
+        }
+    }
+}
+
+fn spawn_particle_bursts(
+    mut commands: Commands,
+    mut rng: ResMut<ParticleRng>,
+    mut spawn_events: EventReader<SpawnParticles>,
+) {
+    for event in spawn_events.read() {
+        for _ in 0..event.effect.count {
+            spawn_particle(&mut commands, &mut rng, event.position, &event.effect);
+        }
+    }
+}
+
+fn update_emitters(
+    mut commands: Commands,
+    mut rng: ResMut<ParticleRng>,
+    mut emitter_query: Query<(Entity, &mut ParticleEmitter, &GlobalTransform)>,
+    time: Res<Time>,
+) {
+    for (entity, mut emitter, transform) in emitter_query.iter_mut() {
+        if let Some(lifetime) = emitter.lifetime.as_mut() {
+            if lifetime.tick(time.delta()).finished() {
+                commands.entity(entity).despawn_recursive();
+                continue;
+            }
+        }
+        if !emitter.active {
+            emitter.accumulator = 0.0;
+            continue;
+        }
+        emitter.accumulator += emitter.rate * time.delta_seconds();
+        while emitter.accumulator >= 1.0 {
+            emitter.accumulator -= 1.0;
+            spawn_particle(
+                &mut commands,
+                &mut rng,
+                transform.translation(),
+                &emitter.effect,
+            );
+        }
+    }
+}
+
+fn update_particles(
+    mut commands: Commands,
+    mut particle_query: Query<(
+        Entity,
+        &mut Particle,
+        &mut Transform,
+        Option<&mut Sprite>,
+        Option<&mut TextureAtlasSprite>,
+    )>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_seconds();
+    for (entity, mut particle, mut transform, sprite, atlas_sprite) in particle_query.iter_mut() {
+        particle.age += delta;
+        if particle.age >= particle.lifetime {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        particle.velocity.y += particle.gravity * delta;
+        transform.translation += (particle.velocity * delta).extend(0.0);
+
+        let t = particle.age / particle.lifetime;
+        let start = particle.start_color.as_rgba_f32();
+        let end = particle.end_color.as_rgba_f32();
+        let color = Color::rgba(
+            start[0] + (end[0] - start[0]) * t,
+            start[1] + (end[1] - start[1]) * t,
+            start[2] + (end[2] - start[2]) * t,
+            start[3] + (end[3] - start[3]) * t,
+        );
+        if let Some(mut sprite) = sprite {
+            sprite.color = color;
+        }
+        if let Some(mut atlas_sprite) = atlas_sprite {
+            atlas_sprite.color = color;
+            if let Some((first, last)) = particle.frames {
+                atlas_sprite.index = first + ((last - first + 1) as f32 * t) as usize;
+            }
+        }
+    }
+}
+
+fn feet_position(transform: &GlobalTransform) -> Vec3 {
+    transform.translation() + Vec3::new(0.0, -PLAYER_FEET_OFFSET, 1.0)
+}
+
+fn landing_dust(
+    mut landed_events: EventReader<PlayerLanded>,
+    mut spawn_events: EventWriter<SpawnParticles>,
+    transform_query: Query<&GlobalTransform>,
+) {
+    for event in landed_events.read() {
+        let Ok(transform) = transform_query.get(event.entity) else {
+            continue;
+        };
+        let mut effect = ParticleEffect::landing_dust();
+        effect.count += (event.impact_speed / 40.0) as u32;
+        spawn_events.send(SpawnParticles {
+            position: feet_position(transform),
+            effect,
+        });
+    }
+}
+
+fn jump_puffs(
+    mut jumped_events: EventReader<PlayerJumped>,
+    mut spawn_events: EventWriter<SpawnParticles>,
+    transform_query: Query<&GlobalTransform>,
+) {
+    for event in jumped_events.read() {
+        let Ok(transform) = transform_query.get(event.entity) else {
+            continue;
+        };
+        spawn_events.send(SpawnParticles {
+            position: feet_position(transform),
+            effect: ParticleEffect::jump_puff(),
+        });
+    }
+}
+
+/// Marks the emitter kicking up dust behind a running player.
+#[derive(Component)]
+pub struct RunDustEmitter;
+
+fn attach_run_dust_emitters(
+    mut commands: Commands,
+    sprite_query: Query<(Entity, &Transform), Added<PlayerSprite>>,
+) {
+    for (sprite, transform) in sprite_query.iter() {
+        let mut emitter = ParticleEmitter::new(ParticleEffect::run_dust(), 12.0);
+        emitter.active = false;
+        commands.entity(sprite).with_children(|parent| {
+            parent.spawn((
+                RunDustEmitter,
+                emitter,
+                SpatialBundle::from_transform(Transform::from_xyz(
+                    0.0,
+                    -PLAYER_FEET_OFFSET / transform.scale.y,
+                    -1.0,
+                )),
+            ));
+        });
+    }
+}
+
+/// Enables the dust emitter of a player sprite while its run animation plays.
+fn run_dust(
+    mut emitter_query: Query<(&mut ParticleEmitter, &Parent), With<RunDustEmitter>>,
+    animator_query: Query<&Animator>,
+) {
+    for (mut emitter, parent) in emitter_query.iter_mut() {
+        let running = animator_query
+            .get(parent.get())
+            .map(|animator| animator.state() == Some("run"))
+            .unwrap_or(false);
+        if emitter.active != running {
+            emitter.active = running;
+        }
+    }
+}
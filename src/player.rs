@@ -1,30 +1,144 @@
 use bevy::prelude::*;
 use bevy_rapier2d::control::{KinematicCharacterController, KinematicCharacterControllerOutput};
 
-use crate::input::GameInputEvent;
+use crate::input::{GameInput, GameInputEvent};
 use crate::state::ScheduleSet;
 
+/// Distance from the center of the player collider to its feet.
+pub const PLAYER_FEET_OFFSET: f32 = 18.0;
+
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update,
-            (
-                (gravity, move_horizontal, jump).in_set(ScheduleSet::MainUpdate),
-                (reset_velocity_on_collision,).in_set(ScheduleSet::VelocityCorrection),
-                (apply_velocity,).in_set(ScheduleSet::TransformUpdate),
-            ),
-        );
+        app.add_event::<PlayerLanded>()
+            .add_event::<PlayerJumped>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    restore_physics_transforms.before(ScheduleSet::SampleInput),
+                    (detect_landing, (gravity, move_horizontal, jump))
+                        .chain()
+                        .in_set(ScheduleSet::MainUpdate),
+                    (reset_velocity_on_collision,).in_set(ScheduleSet::VelocityCorrection),
+                    (apply_velocity,).in_set(ScheduleSet::TransformUpdate),
+                    snapshot_physics_transforms.after(ScheduleSet::WritebackRapier),
+                ),
+            )
+            .add_systems(
+                Update,
+                (interpolate_physics_transforms,).in_set(ScheduleSet::Interpolate),
+            );
     }
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
 pub struct Player;
 
+/// Which local player controls the entity. Player 0 is placed in the LDtk
+/// level, the others join with a gamepad.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default, Component)]
+pub struct PlayerId(pub usize);
+
 #[derive(Component, Clone, Debug, Default)]
 pub struct Velocity(pub Vec2);
 
+/// Translation of the last two simulation steps. Between steps the transform is
+/// interpolated for rendering, and restored before the next step runs so the
+/// simulation never sees the interpolated value.
+#[derive(Component, Clone, Debug, Default)]
+pub struct PhysicsInterpolation {
+    previous: Vec3,
+    current: Option<Vec3>,
+}
+
+impl PhysicsInterpolation {
+    /// Takes the transform as it is on the next step, for moving the entity
+    /// without interpolating from its old position.
+    pub fn reset(&mut self) {
+        self.current = None;
+    }
+}
+
+pub fn restore_physics_transforms(mut query: Query<(&mut Transform, &mut PhysicsInterpolation)>) {
+    for (mut transform, mut interpolation) in query.iter_mut() {
+        match interpolation.current {
+            Some(current) => {
+                if transform.translation != current {
+                    transform.translation = current;
+                }
+            }
+            None => {
+                interpolation.previous = transform.translation;
+                interpolation.current = Some(transform.translation);
+            }
+        }
+    }
+}
+
+pub fn snapshot_physics_transforms(mut query: Query<(&Transform, &mut PhysicsInterpolation)>) {
+    for (transform, mut interpolation) in query.iter_mut() {
+        interpolation.previous = interpolation.current.unwrap_or(transform.translation);
+        interpolation.current = Some(transform.translation);
+    }
+}
+
+pub fn interpolate_physics_transforms(
+    mut query: Query<(&mut Transform, &PhysicsInterpolation)>,
+    fixed_time: Res<Time<Fixed>>,
+) {
+    let t = fixed_time.overstep_percentage();
+    for (mut transform, interpolation) in query.iter_mut() {
+        if let Some(current) = interpolation.current {
+            transform.translation = interpolation.previous.lerp(current, t);
+        }
+    }
+}
+
+/// Grounded flag of the previous step, used to detect landings.
+#[derive(Component, Clone, Debug, Default)]
+pub struct GroundState {
+    pub grounded: bool,
+}
+
+/// Sent when a player touches the ground after being airborne.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct PlayerLanded {
+    pub entity: Entity,
+    pub impact_speed: f32,
+}
+
+/// Sent when a player leaves the ground by jumping.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct PlayerJumped {
+    pub entity: Entity,
+}
+
+/// Runs before the velocity is corrected for collisions, so the falling speed
+/// at the moment of impact is still available.
+pub fn detect_landing(
+    mut query: Query<
+        (
+            Entity,
+            &Velocity,
+            &mut GroundState,
+            &KinematicCharacterControllerOutput,
+        ),
+        With<Player>,
+    >,
+    mut landed_events: EventWriter<PlayerLanded>,
+) {
+    for (entity, velocity, mut ground_state, charachter_controller) in query.iter_mut() {
+        if charachter_controller.grounded && !ground_state.grounded {
+            landed_events.send(PlayerLanded {
+                entity,
+                impact_speed: -velocity.0.y.min(0.0),
+            });
+        }
+        ground_state.grounded = charachter_controller.grounded;
+    }
+}
+
 pub fn reset_velocity_on_collision(
     mut query: Query<(&mut Velocity, &KinematicCharacterControllerOutput), With<Player>>,
 ) {
@@ -49,20 +163,17 @@ pub fn reset_velocity_on_collision(
 }
 
 pub fn apply_velocity(
-    velocity_query: Query<&mut Velocity, With<Player>>,
-    mut charachter_controller_query: Query<&mut KinematicCharacterController, With<Player>>,
+    mut query: Query<(&Velocity, &mut KinematicCharacterController), With<Player>>,
     time: Res<Time>,
 ) {
-    for velocity in velocity_query.iter() {
-        for mut charachter_controller in charachter_controller_query.iter_mut() {
-            match charachter_controller.translation {
-                Some(translation) => {
-                    charachter_controller.translation =
-                        Some(translation + velocity.0 * time.delta_seconds());
-                }
-                None => {
-                    charachter_controller.translation = Some(velocity.0 * time.delta_seconds());
-                }
+    for (velocity, mut charachter_controller) in query.iter_mut() {
+        match charachter_controller.translation {
+            Some(translation) => {
+                charachter_controller.translation =
+                    Some(translation + velocity.0 * time.delta_seconds());
+            }
+            None => {
+                charachter_controller.translation = Some(velocity.0 * time.delta_seconds());
             }
         }
     }
@@ -81,18 +192,19 @@ pub fn gravity(
 }
 
 pub fn move_horizontal(
-    mut query: Query<&mut Velocity, With<Player>>,
+    mut query: Query<(&PlayerId, &mut Velocity), With<Player>>,
     time: Res<Time>,
     mut input_events: EventReader<GameInputEvent>,
 ) {
     let acceleration = 500.0;
     let max_speed: f32 = 170.0;
 
-    let mut active_movement = false;
-    for event in input_events.read() {
-        match event {
-            GameInputEvent::MoveLeft(input_strength) => {
-                for mut velocity in query.iter_mut() {
+    let events: Vec<&GameInputEvent> = input_events.read().collect();
+    for (player_id, mut velocity) in query.iter_mut() {
+        let mut active_movement = false;
+        for event in events.iter().filter(|event| event.player == *player_id) {
+            match event.input {
+                GameInput::MoveLeft(input_strength) => {
                     if velocity.0.x > 0.0 {
                         velocity.0.x = 0.0;
                     }
@@ -100,9 +212,7 @@ pub fn move_horizontal(
                         .max(velocity.0.x - acceleration * time.delta_seconds());
                     active_movement = true;
                 }
-            }
-            GameInputEvent::MoveRight(input_strength) => {
-                for mut velocity in query.iter_mut() {
+                GameInput::MoveRight(input_strength) => {
                     if velocity.0.x < 0.0 {
                         velocity.0.x = 0.0;
                     }
@@ -110,26 +220,38 @@ pub fn move_horizontal(
                         .min(velocity.0.x + acceleration * time.delta_seconds());
                     active_movement = true;
                 }
+                _ => {}
             }
-            _ => {}
         }
-    }
-    if !active_movement {
-        for mut velocity in query.iter_mut() {
+        if !active_movement {
             velocity.0.x = 0.0;
         }
     }
 }
 
 pub fn jump(
-    mut query: Query<(&mut Velocity, &KinematicCharacterControllerOutput), With<Player>>,
+    mut query: Query<
+        (
+            Entity,
+            &PlayerId,
+            &mut Velocity,
+            &KinematicCharacterControllerOutput,
+        ),
+        With<Player>,
+    >,
     mut input_events: EventReader<GameInputEvent>,
+    mut jumped_events: EventWriter<PlayerJumped>,
 ) {
     for event in input_events.read() {
-        match event {
-            GameInputEvent::Jump => {
-                for (mut velocity, charachter_controller) in query.iter_mut() {
-                    if charachter_controller.grounded {
+        match event.input {
+            GameInput::Jump => {
+                for (entity, player_id, mut velocity, charachter_controller) in query.iter_mut() {
+                    if *player_id == event.player && charachter_controller.grounded {
+                        // Holding jump sets the velocity again on the next
+                        // grounded step, that is not another jump.
+                        if velocity.0.y <= 0.0 {
+                            jumped_events.send(PlayerJumped { entity });
+                        }
                         velocity.0.y = 200.0;
                     }
                 }
@@ -0,0 +1,667 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+use crate::bindings::{Action, ActionInput};
+use crate::coop::{JoinedPlayers, PlayerSlots};
+use crate::input::{GameInput, GameInputEvent};
+use crate::player::{Player, PlayerId};
+use crate::settings::{config_path, SettingsError};
+use crate::state::ScheduleSet;
+
+const MAGIC: &[u8; 4] = b"PLRP";
+const FORMAT_VERSION: u16 = 2;
+/// Version 1 stored runs as `u16` and counts and players as `u8`.
+const FIXED_WIDTH_VERSION: u16 = 1;
+/// Written when a recording is stopped and played back with `Action::PlayReplay`.
+const LAST_REPLAY: &str = "replays/last.replay";
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReplayState>()
+            .init_resource::<PlayersReady>()
+            .insert_resource(SimulationSeed(new_seed()))
+            .add_systems(
+                FixedUpdate,
+                (
+                    update_players_ready.before(ScheduleSet::SampleInput),
+                    play_inputs
+                        .run_if(players_ready)
+                        .in_set(ScheduleSet::SampleInput),
+                    record_inputs.in_set(ScheduleSet::MainUpdate),
+                ),
+            )
+            .add_systems(
+                Update,
+                (start_pending_replay, handle_replay_actions).in_set(ScheduleSet::HandleInput),
+            );
+
+        // `--replay <file>` plays a recording as soon as a game is started.
+        let mut args = std::env::args().skip_while(|arg| arg != "--replay").skip(1);
+        if let Some(path) = args.next() {
+            match std::fs::read(&path)
+                .map_err(ReplayError::from)
+                .and_then(|bytes| Replay::decode(&bytes))
+            {
+                Ok(replay) => {
+                    app.insert_resource(PendingReplay(replay));
+                }
+                Err(error) => warn!("Failed to load replay {}: {}", path, error),
+            }
+        }
+    }
+}
+
+/// Seed for randomness, stored in replays so playback can restore it. The
+/// simulation has no randomness yet, particles are seeded from it so playback
+/// also shows the same effects.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimulationSeed(pub u64);
+
+fn new_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    #[error("could not access replay file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Config(#[from] SettingsError),
+    #[error("not a replay file")]
+    BadMagic,
+    #[error("unsupported replay version {0}")]
+    UnsupportedVersion(u16),
+    #[error("replay file is truncated")]
+    Truncated,
+    #[error("unknown input code {0}")]
+    UnknownInput(u8),
+    #[error("malformed number in replay file")]
+    MalformedVarint,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayHeader {
+    /// Iid of the level the recording started in.
+    pub level: String,
+    /// `CARGO_PKG_VERSION` of the recording build, other builds may desync.
+    pub build: String,
+    pub seed: u64,
+}
+
+/// Inputs of every simulation step, in the order the steps ran.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub header: ReplayHeader,
+    pub ticks: Vec<Vec<GameInputEvent>>,
+}
+
+/// Only inputs read by the simulation are recorded, zoom and menus are not.
+fn is_recorded(input: &GameInput) -> bool {
+    matches!(
+        input,
+        GameInput::MoveLeft(_)
+            | GameInput::MoveRight(_)
+            | GameInput::Jump
+            | GameInput::ResetLevel
+            | GameInput::Join
+            | GameInput::Leave
+    )
+}
+
+impl Replay {
+    /// Little endian header followed by runs of identical ticks, so holding a
+    /// direction takes a single entry:
+    /// `magic, version: u16, level, build, seed: u64, (run, count, (player, input)*)*`
+    /// where strings are a `u16` length followed by UTF-8 bytes and runs, counts
+    /// and players are LEB128 varints.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        write_string(&mut bytes, &self.header.level);
+        write_string(&mut bytes, &self.header.build);
+        bytes.extend_from_slice(&self.header.seed.to_le_bytes());
+
+        let mut ticks = self.ticks.iter().peekable();
+        while let Some(tick) = ticks.next() {
+            let mut run = 1;
+            while ticks.peek() == Some(&tick) {
+                ticks.next();
+                run += 1;
+            }
+            write_varint(&mut bytes, run);
+            write_varint(&mut bytes, tick.len() as u64);
+            for event in tick {
+                write_varint(&mut bytes, event.player.0 as u64);
+                match event.input {
+                    GameInput::MoveLeft(strength) => {
+                        bytes.push(0);
+                        bytes.extend_from_slice(&strength.to_le_bytes());
+                    }
+                    GameInput::MoveRight(strength) => {
+                        bytes.push(1);
+                        bytes.extend_from_slice(&strength.to_le_bytes());
+                    }
+                    GameInput::Jump => bytes.push(2),
+                    GameInput::ResetLevel => bytes.push(3),
+                    GameInput::Join => bytes.push(4),
+                    GameInput::Leave => bytes.push(5),
+                    _ => unreachable!("only recorded inputs are stored"),
+                }
+            }
+        }
+        bytes
+    }
+
+    /// Also reads version 1 recordings.
+    pub fn decode(bytes: &[u8]) -> Result<Replay, ReplayError> {
+        let mut reader = ByteReader { bytes, position: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(ReplayError::BadMagic);
+        }
+        let version = reader.u16()?;
+        if version != FORMAT_VERSION && version != FIXED_WIDTH_VERSION {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+        let fixed_width = version == FIXED_WIDTH_VERSION;
+        let header = ReplayHeader {
+            level: reader.string()?,
+            build: reader.string()?,
+            seed: reader.u64()?,
+        };
+
+        let mut ticks = Vec::new();
+        while !reader.is_empty() {
+            let (run, count) = if fixed_width {
+                (reader.u16()? as u64, reader.u8()? as u64)
+            } else {
+                (reader.varint()?, reader.varint()?)
+            };
+            let mut tick = Vec::new();
+            for _ in 0..count {
+                let player = if fixed_width {
+                    reader.u8()? as u64
+                } else {
+                    reader.varint()?
+                };
+                let player = PlayerId(player as usize);
+                let input = match reader.u8()? {
+                    0 => GameInput::MoveLeft(reader.f32()?),
+                    1 => GameInput::MoveRight(reader.f32()?),
+                    2 => GameInput::Jump,
+                    3 => GameInput::ResetLevel,
+                    4 => GameInput::Join,
+                    5 => GameInput::Leave,
+                    code => return Err(ReplayError::UnknownInput(code)),
+                };
+                tick.push(GameInputEvent { player, input });
+            }
+            ticks.extend(std::iter::repeat(tick).take(run as usize));
+        }
+        Ok(Replay { header, ticks })
+    }
+
+    pub fn save(&self, file_name: &str) -> Result<(), ReplayError> {
+        let path = config_path(file_name)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.encode())?;
+        Ok(())
+    }
+
+    pub fn load(file_name: &str) -> Result<Replay, ReplayError> {
+        Replay::decode(&std::fs::read(config_path(file_name)?)?)
+    }
+}
+
+fn write_string(bytes: &mut Vec<u8>, value: &str) {
+    let value = &value.as_bytes()[..value.len().min(u16::MAX as usize)];
+    bytes.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(value);
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn is_empty(&self) -> bool {
+        self.position >= self.bytes.len()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ReplayError> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + len)
+            .ok_or(ReplayError::Truncated)?;
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ReplayError> {
+        Ok(self
+            .take(N)?
+            .try_into()
+            .expect("slice has the requested length"))
+    }
+
+    fn u8(&mut self) -> Result<u8, ReplayError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ReplayError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, ReplayError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn f32(&mut self) -> Result<f32, ReplayError> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+
+    fn varint(&mut self) -> Result<u64, ReplayError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(ReplayError::MalformedVarint)
+    }
+
+    fn string(&mut self) -> Result<String, ReplayError> {
+        let len = self.u16()? as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+}
+
+/// Recording or playback in progress. During playback the live movement input
+/// is ignored, see `not_replaying`.
+#[derive(Resource, Debug, Default)]
+pub enum ReplayState {
+    #[default]
+    Idle,
+    Recording(Replay),
+    Playing {
+        replay: Replay,
+        tick: usize,
+    },
+}
+
+pub fn not_replaying(state: Res<ReplayState>) -> bool {
+    !matches!(*state, ReplayState::Playing { .. })
+}
+
+/// Replay passed on the command line, started once the world exists.
+#[derive(Resource, Debug)]
+struct PendingReplay(Replay);
+
+/// Whether player 0 exists and no level is waiting to respawn. Respawns take
+/// a varying number of frames, so steps before that are neither recorded nor
+/// played back, otherwise inputs would reach the players at different steps.
+#[derive(Resource, Debug, Default)]
+struct PlayersReady(bool);
+
+pub fn players_ready(ready: Res<PlayersReady>) -> bool {
+    ready.0
+}
+
+fn update_players_ready(
+    mut ready: ResMut<PlayersReady>,
+    respawn_query: Query<(), With<Respawn>>,
+    player_query: Query<&PlayerId, With<Player>>,
+) {
+    ready.0 = respawn_query.is_empty()
+        && player_query
+            .iter()
+            .any(|player_id| *player_id == PlayerId(0));
+}
+
+/// Runs with the simulation steps, after all inputs of the step were sampled,
+/// so the recording has exactly one entry per step that playback consumes.
+/// Players that joined before the recording started join at its first step.
+fn record_inputs(
+    mut state: ResMut<ReplayState>,
+    mut input_events: EventReader<GameInputEvent>,
+    ready: Res<PlayersReady>,
+    joined: Res<JoinedPlayers>,
+) {
+    let ReplayState::Recording(replay) = &mut *state else {
+        input_events.clear();
+        return;
+    };
+    if !ready.0 {
+        input_events.clear();
+        return;
+    }
+    let mut tick: Vec<_> = input_events
+        .read()
+        .filter(|event| is_recorded(&event.input))
+        .copied()
+        .collect();
+    if replay.ticks.is_empty() {
+        let joins: Vec<_> = joined
+            .0
+            .iter()
+            .map(|player| GameInputEvent {
+                player: *player,
+                input: GameInput::Join,
+            })
+            .filter(|join| !tick.contains(join))
+            .collect();
+        tick.splice(0..0, joins);
+    }
+    replay.ticks.push(tick);
+}
+
+fn play_inputs(mut state: ResMut<ReplayState>, mut input_events: EventWriter<GameInputEvent>) {
+    let ReplayState::Playing { replay, tick } = &mut *state else {
+        return;
+    };
+    match replay.ticks.get(*tick) {
+        Some(events) => {
+            input_events.send_batch(events.iter().copied());
+            *tick += 1;
+        }
+        None => {
+            info!("Replay finished after {} steps", tick);
+            *state = ReplayState::Idle;
+        }
+    }
+}
+
+/// Iid of the selected level, recordings start from there.
+fn selected_level_iid(
+    level_selection: &LevelSelection,
+    project: Option<&LdtkProject>,
+) -> Option<String> {
+    project?
+        .iter_raw_levels()
+        .find(|level| level_selection.is_match(&LevelIndices::default(), level))
+        .map(|level| level.iid.clone())
+}
+
+/// Respawns the world in the replay's level so recording and playback start
+/// from the same state. Joined players are removed, the replay joins them
+/// again, and gamepads join anew once it finished.
+fn begin_playback(
+    replay: Replay,
+    commands: &mut Commands,
+    world: Entity,
+    level_selection: &mut LevelSelection,
+    seed: &mut SimulationSeed,
+    state: &mut ReplayState,
+) {
+    if replay.header.build != env!("CARGO_PKG_VERSION") {
+        warn!(
+            "Replay was recorded with version {}, playback may desync",
+            replay.header.build
+        );
+    }
+    *level_selection = LevelSelection::iid(replay.header.level.clone());
+    *seed = SimulationSeed(replay.header.seed);
+    commands.insert_resource(PlayerSlots::default());
+    commands.insert_resource(JoinedPlayers::default());
+    commands.entity(world).insert(Respawn);
+    info!("Playing replay of {} steps", replay.ticks.len());
+    *state = ReplayState::Playing { replay, tick: 0 };
+}
+
+fn start_pending_replay(
+    mut commands: Commands,
+    pending: Option<Res<PendingReplay>>,
+    world_query: Query<Entity, With<Handle<LdtkProject>>>,
+    mut level_selection: ResMut<LevelSelection>,
+    mut seed: ResMut<SimulationSeed>,
+    mut state: ResMut<ReplayState>,
+) {
+    let (Some(pending), Ok(world)) = (pending, world_query.get_single()) else {
+        return;
+    };
+    commands.remove_resource::<PendingReplay>();
+    begin_playback(
+        pending.0.clone(),
+        &mut commands,
+        world,
+        &mut level_selection,
+        &mut seed,
+        &mut state,
+    );
+}
+
+/// `Action::ToggleRecording` starts a recording or stops and saves it,
+/// `Action::PlayReplay` plays the last saved recording.
+fn handle_replay_actions(
+    mut commands: Commands,
+    actions: ActionInput,
+    world_query: Query<(Entity, &Handle<LdtkProject>)>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    mut level_selection: ResMut<LevelSelection>,
+    mut seed: ResMut<SimulationSeed>,
+    mut state: ResMut<ReplayState>,
+) {
+    let Ok((world, project)) = world_query.get_single() else {
+        return;
+    };
+
+    if actions.just_pressed(Action::ToggleRecording) {
+        match std::mem::take(&mut *state) {
+            ReplayState::Recording(replay) => match replay.save(LAST_REPLAY) {
+                Ok(()) => info!("Saved replay of {} steps", replay.ticks.len()),
+                Err(error) => warn!("Failed to save replay: {}", error),
+            },
+            _ => {
+                let Some(level) =
+                    selected_level_iid(&level_selection, ldtk_project_assets.get(project))
+                else {
+                    warn!("Cannot record, the selected level is unknown");
+                    return;
+                };
+                *seed = SimulationSeed(new_seed());
+                *level_selection = LevelSelection::iid(level.clone());
+                commands.entity(world).insert(Respawn);
+                info!("Recording replay");
+                *state = ReplayState::Recording(Replay {
+                    header: ReplayHeader {
+                        level,
+                        build: env!("CARGO_PKG_VERSION").to_string(),
+                        seed: seed.0,
+                    },
+                    ticks: Vec::new(),
+                });
+            }
+        }
+    } else if actions.just_pressed(Action::PlayReplay) {
+        match Replay::load(LAST_REPLAY) {
+            Ok(replay) => begin_playback(
+                replay,
+                &mut commands,
+                world,
+                &mut level_selection,
+                &mut seed,
+                &mut state,
+            ),
+            Err(error) => warn!("Failed to load replay: {}", error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(player: usize, input: GameInput) -> GameInputEvent {
+        GameInputEvent {
+            player: PlayerId(player),
+            input,
+        }
+    }
+
+    fn replay(ticks: Vec<Vec<GameInputEvent>>) -> Replay {
+        Replay {
+            header: ReplayHeader {
+                level: "level-iid".to_string(),
+                build: "0.1.0".to_string(),
+                seed: 0x0123_4567_89ab_cdef,
+            },
+            ticks,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let replay = replay(vec![
+            vec![event(0, GameInput::Join), event(1, GameInput::Join)],
+            vec![event(0, GameInput::MoveLeft(0.5))],
+            vec![event(0, GameInput::MoveLeft(0.5))],
+            vec![],
+            vec![
+                event(1, GameInput::MoveRight(1.0)),
+                event(0, GameInput::Jump),
+            ],
+            vec![event(0, GameInput::ResetLevel), event(1, GameInput::Leave)],
+        ]);
+        assert_eq!(Replay::decode(&replay.encode()).unwrap(), replay);
+    }
+
+    #[test]
+    fn empty_replay_round_trips() {
+        let replay = replay(Vec::new());
+        assert_eq!(Replay::decode(&replay.encode()).unwrap(), replay);
+    }
+
+    #[test]
+    fn identical_ticks_are_stored_as_one_run() {
+        let tick = vec![event(0, GameInput::MoveRight(1.0))];
+        let short = replay(vec![tick.clone()]).encode();
+        let long = replay(vec![tick; 1000]).encode();
+        // Only the run length grows, from one to two varint bytes.
+        assert_eq!(long.len(), short.len() + 1);
+    }
+
+    #[test]
+    fn runs_longer_than_u16_round_trip() {
+        let tick = vec![event(0, GameInput::MoveLeft(1.0))];
+        for len in [127, 128, u16::MAX as usize, u16::MAX as usize + 1] {
+            let replay = replay(vec![tick.clone(); len]);
+            assert_eq!(Replay::decode(&replay.encode()).unwrap(), replay);
+        }
+    }
+
+    #[test]
+    fn ticks_with_many_events_round_trip() {
+        let tick = (0..300)
+            .map(|player| event(player, GameInput::Jump))
+            .collect();
+        let replay = replay(vec![tick]);
+        assert_eq!(Replay::decode(&replay.encode()).unwrap(), replay);
+    }
+
+    #[test]
+    fn large_player_ids_round_trip() {
+        let replay = replay(vec![vec![event(1 << 20, GameInput::Join)]]);
+        assert_eq!(Replay::decode(&replay.encode()).unwrap(), replay);
+    }
+
+    #[test]
+    fn truncated_input_is_an_error() {
+        let ticks = vec![
+            vec![event(0, GameInput::MoveLeft(0.5))],
+            vec![event(300, GameInput::Jump)],
+        ];
+        let bytes = replay(ticks.clone()).encode();
+        // Cutting between two ticks leaves a valid shorter replay.
+        let tick_ends: Vec<_> = (0..ticks.len())
+            .map(|len| replay(ticks[..len].to_vec()).encode().len())
+            .collect();
+        for len in (0..bytes.len()).filter(|len| !tick_ends.contains(len)) {
+            assert!(
+                matches!(Replay::decode(&bytes[..len]), Err(ReplayError::Truncated)),
+                "decoding {} of {} bytes",
+                len,
+                bytes.len()
+            );
+        }
+    }
+
+    #[test]
+    fn bad_magic_is_an_error() {
+        let mut bytes = replay(Vec::new()).encode();
+        bytes[0] = b'X';
+        assert!(matches!(Replay::decode(&bytes), Err(ReplayError::BadMagic)));
+    }
+
+    #[test]
+    fn unknown_versions_are_an_error() {
+        let mut bytes = replay(Vec::new()).encode();
+        bytes[4..6].copy_from_slice(&7u16.to_le_bytes());
+        assert!(matches!(
+            Replay::decode(&bytes),
+            Err(ReplayError::UnsupportedVersion(7))
+        ));
+    }
+
+    #[test]
+    fn version_1_is_decoded() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FIXED_WIDTH_VERSION.to_le_bytes());
+        write_string(&mut bytes, "level-iid");
+        write_string(&mut bytes, "0.1.0");
+        bytes.extend_from_slice(&0x0123_4567_89ab_cdefu64.to_le_bytes());
+        bytes.extend_from_slice(&300u16.to_le_bytes());
+        bytes.extend_from_slice(&[1, 0, 1]);
+        bytes.extend_from_slice(&0.5f32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&[1, 2, 2]);
+
+        let mut ticks = vec![vec![event(0, GameInput::MoveRight(0.5))]; 300];
+        ticks.push(vec![event(2, GameInput::Jump)]);
+        assert_eq!(Replay::decode(&bytes).unwrap(), replay(ticks));
+    }
+
+    #[test]
+    fn varints_round_trip() {
+        for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let mut bytes = Vec::new();
+            write_varint(&mut bytes, value);
+            let mut reader = ByteReader {
+                bytes: &bytes,
+                position: 0,
+            };
+            assert_eq!(reader.varint().unwrap(), value);
+            assert!(reader.is_empty());
+        }
+    }
+
+    #[test]
+    fn overlong_varints_are_an_error() {
+        let bytes = [0xff; 11];
+        let mut reader = ByteReader {
+            bytes: &bytes,
+            position: 0,
+        };
+        assert!(matches!(reader.varint(), Err(ReplayError::MalformedVarint)));
+    }
+}
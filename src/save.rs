@@ -0,0 +1,590 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+use crate::input::{GameInput, GameInputEvent};
+use crate::menu_builder::{MenuActivated, MenuPage, MenuSet, MenuValues};
+use crate::player::{PhysicsInterpolation, Player, PlayerId, PlayerJumped};
+use crate::settings::{read_config, write_config, SettingsError};
+use crate::state::{AppState, ScheduleSet, ShutdownSet};
+use crate::world::{level_name, LevelAssets, LevelChanged, LevelFinished, Pickup, PickupCollected};
+
+pub const SAVE_SLOTS: usize = 3;
+const SLOT_IDS: [&str; SAVE_SLOTS] = ["slot_0", "slot_1", "slot_2"];
+const SLOT_LABELS: [&str; SAVE_SLOTS] = ["save.slot_1", "save.slot_2", "save.slot_3"];
+
+/// Layout of `SaveGame` written by this build, older files are upgraded by
+/// `migrate` when they are loaded.
+pub const SAVE_VERSION: u32 = 1;
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SaveSlots::load())
+            .init_resource::<ActiveSave>()
+            .init_resource::<PendingPlacement>()
+            .add_systems(
+                Update,
+                (
+                    place_player.before(ScheduleSet::HandleInput),
+                    (
+                        track_play_time,
+                        count_stats,
+                        collect_items,
+                        remove_collected_pickups,
+                        finish_level,
+                        reach_checkpoint,
+                    )
+                        .chain()
+                        .after(ScheduleSet::HandleInput)
+                        .run_if(in_state(AppState::Playing)),
+                    sync_save_slot_values.before(MenuSet),
+                    handle_title_save_events
+                        .after(MenuSet)
+                        .run_if(in_state(AppState::TitleScreen)),
+                ),
+            )
+            .add_systems(OnEnter(AppState::GameOver), count_death)
+            .add_systems(OnEnter(AppState::TitleScreen), close_save)
+            .add_systems(Last, flush_save.in_set(ShutdownSet));
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SaveError {
+    #[error("{0}")]
+    Config(#[from] SettingsError),
+    #[error("could not read save: {0}")]
+    Value(#[from] ron::Error),
+    #[error("save version {0} is newer than this build")]
+    UnsupportedVersion(u32),
+}
+
+/// Progress through one level.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct LevelRecord {
+    pub completed: bool,
+    /// Fastest time from entering to leaving the level, in seconds.
+    pub best_time: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct SaveStats {
+    pub jumps: u32,
+    pub deaths: u32,
+    pub level_restarts: u32,
+}
+
+/// One save slot, stored in `<config dir>/platformer/saves/slot_<n>.ron`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct SaveGame {
+    pub version: u32,
+    /// Unix time of the last save, Continue loads the latest slot.
+    pub saved_at: u64,
+    /// Iid of the level player 0 was in.
+    pub level: Option<String>,
+    /// Translation of player 0 when that level was entered. Levels have no
+    /// checkpoints of their own, so in a game with a single level this is
+    /// where player 0 spawned.
+    pub checkpoint: Option<Vec3>,
+    /// Iids of collected `Pickup` entities, they are not spawned again.
+    pub collected_items: BTreeSet<String>,
+    /// Abilities unlocked by pickups. No ability changes the gameplay yet.
+    pub abilities: BTreeSet<String>,
+    /// Game time in seconds, pauses excluded.
+    pub play_time: f32,
+    pub stats: SaveStats,
+    pub levels: BTreeMap<String, LevelRecord>,
+}
+
+impl Default for SaveGame {
+    fn default() -> Self {
+        SaveGame {
+            version: SAVE_VERSION,
+            saved_at: 0,
+            level: None,
+            checkpoint: None,
+            collected_items: BTreeSet::new(),
+            abilities: BTreeSet::new(),
+            play_time: 0.0,
+            stats: SaveStats::default(),
+            levels: BTreeMap::new(),
+        }
+    }
+}
+
+/// Upgrades a save from `version` to `version + 1`. Add a step whenever a
+/// change to `SaveGame` can not be covered by `#[serde(default)]`, and bump
+/// `SAVE_VERSION`.
+fn migrate(version: u32, save: ron::Value) -> Result<ron::Value, SaveError> {
+    match version {
+        // Saves written before versioning have the same fields, only the
+        // version is missing.
+        0 => Ok(with_version(save, 1)),
+        _ => Err(SaveError::UnsupportedVersion(version)),
+    }
+}
+
+fn with_version(save: ron::Value, version: u32) -> ron::Value {
+    let ron::Value::Map(mut map) = save else {
+        return save;
+    };
+    map.insert(
+        ron::Value::String("version".to_string()),
+        ron::Value::Number(ron::Number::Integer(version.into())),
+    );
+    ron::Value::Map(map)
+}
+
+/// Version of a save, `None` for saves written before versioning.
+fn version_of(save: &ron::Value) -> Option<u32> {
+    let ron::Value::Map(map) = save else {
+        return None;
+    };
+    match map.get(&ron::Value::String("version".to_string()))? {
+        ron::Value::Number(number) => number.as_i64().and_then(|v| u32::try_from(v).ok()),
+        _ => None,
+    }
+}
+
+impl SaveGame {
+    fn file_name(slot: usize) -> String {
+        format!("saves/slot_{}.ron", slot + 1)
+    }
+
+    /// Reads a slot, `None` if it is empty. Older versions are migrated step by
+    /// step before the save is deserialized.
+    pub fn load(slot: usize) -> Result<Option<SaveGame>, SaveError> {
+        let Some(mut save) = read_config::<ron::Value>(&Self::file_name(slot))? else {
+            return Ok(None);
+        };
+        // Saves without a version predate versioning and are version 0.
+        let mut version = version_of(&save).unwrap_or(0);
+        if version > SAVE_VERSION {
+            return Err(SaveError::UnsupportedVersion(version));
+        }
+        while version < SAVE_VERSION {
+            save = migrate(version, save)?;
+            version += 1;
+        }
+        let mut game: SaveGame = save.into_rust()?;
+        game.version = SAVE_VERSION;
+        Ok(Some(game))
+    }
+
+    pub fn save(&mut self, slot: usize) -> Result<(), SaveError> {
+        self.saved_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        self.write_file(slot)
+    }
+
+    /// Writes the slot without touching `saved_at`, so the slot keeps its
+    /// place in the Continue order.
+    fn write_file(&self, slot: usize) -> Result<(), SaveError> {
+        write_config(&Self::file_name(slot), self)?;
+        Ok(())
+    }
+
+    /// Marks the levels completed in `other` and keeps the best time of each.
+    fn merge_records(&mut self, other: &SaveGame) {
+        for (level, record) in &other.levels {
+            let merged = self.levels.entry(level.clone()).or_default();
+            merged.completed |= record.completed;
+            merged.best_time = match (merged.best_time, record.best_time) {
+                (Some(best), Some(time)) => Some(best.min(time)),
+                (best, time) => best.or(time),
+            };
+        }
+    }
+
+    /// Completes a level that took `time` seconds.
+    fn complete_level(&mut self, level: String, time: f32) {
+        let record = self.levels.entry(level).or_default();
+        record.completed = true;
+        record.best_time = Some(record.best_time.map_or(time, |best| best.min(time)));
+    }
+}
+
+/// Contents of every slot, shown in the slot menu.
+#[derive(Resource, Debug, Default)]
+pub struct SaveSlots {
+    pub slots: [Option<SaveGame>; SAVE_SLOTS],
+}
+
+impl SaveSlots {
+    fn load() -> SaveSlots {
+        let mut slots = SaveSlots::default();
+        for (index, slot) in slots.slots.iter_mut().enumerate() {
+            *slot = SaveGame::load(index).unwrap_or_else(|error| {
+                warn!("Failed to load save slot {}: {}", index + 1, error);
+                None
+            });
+        }
+        slots
+    }
+
+    /// Most recently saved slot.
+    pub fn latest(&self) -> Option<usize> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| Some((index, slot.as_ref()?.saved_at)))
+            .max_by_key(|(_, saved_at)| *saved_at)
+            .map(|(index, _)| index)
+    }
+}
+
+/// Game being played and the slot it is saved to, `None` on the title screen.
+#[derive(Resource, Debug, Default)]
+pub struct ActiveSave {
+    pub slot: Option<usize>,
+    pub game: SaveGame,
+    /// Play time at which the current level was entered.
+    level_entered_at: f32,
+    /// Slot that only receives the level records of the game, see `practice`.
+    records_slot: Option<usize>,
+}
+
+impl ActiveSave {
+    pub fn start(slot: Option<usize>, game: SaveGame) -> Self {
+        ActiveSave {
+            slot,
+            level_entered_at: game.play_time,
+            game,
+            records_slot: None,
+        }
+    }
+
+    /// Game that is not saved, only completed levels and best times are merged
+    /// into `records_slot`. Used for replaying levels, so the level and
+    /// checkpoint of the slot stay where the player got to.
+    pub fn practice(records_slot: Option<usize>, game: SaveGame) -> Self {
+        ActiveSave {
+            records_slot,
+            ..ActiveSave::start(None, game)
+        }
+    }
+
+    fn write(&mut self, slots: &mut SaveSlots) {
+        if let Some(slot) = self.records_slot {
+            self.merge_records(slot, slots);
+        }
+        let Some(slot) = self.slot else {
+            return;
+        };
+        match self.game.save(slot) {
+            Ok(()) => slots.slots[slot] = Some(self.game.clone()),
+            Err(error) => warn!("Failed to save slot {}: {}", slot + 1, error),
+        }
+    }
+
+    /// Practice does not count as playing the slot, so it is written without
+    /// updating `saved_at`.
+    fn merge_records(&self, slot: usize, slots: &mut SaveSlots) {
+        let Some(mut game) = slots.slots[slot].clone() else {
+            return;
+        };
+        game.merge_records(&self.game);
+        if slots.slots[slot].as_ref() == Some(&game) {
+            return;
+        }
+        match game.write_file(slot) {
+            Ok(()) => slots.slots[slot] = Some(game),
+            Err(error) => warn!("Failed to save slot {}: {}", slot + 1, error),
+        }
+    }
+}
+
+/// Where player 0 is moved once it spawns, set when a save is loaded or a level
+/// is picked in the level select. The depth of the player is kept.
+#[derive(Resource, Debug, Default)]
+pub struct PendingPlacement(pub Option<Vec3>);
+
+pub fn slot_page() -> MenuPage {
+    SLOT_IDS
+        .iter()
+        .zip(SLOT_LABELS)
+        .fold(MenuPage::new("save.choose_slot"), |page, (id, label)| {
+            page.button(*id, label)
+        })
+        .back("menu.back")
+}
+
+fn format_play_time(seconds: f32) -> String {
+    let seconds = seconds as u32;
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+fn sync_save_slot_values(
+    slots: Res<SaveSlots>,
+    mut values: ResMut<MenuValues>,
+    level_assets: Option<Res<LevelAssets>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+) {
+    if !slots.is_changed() {
+        return;
+    }
+    let project = level_assets.and_then(|assets| ldtk_project_assets.get(&assets.project));
+    for (id, slot) in SLOT_IDS.iter().zip(&slots.slots) {
+        let text = match slot {
+            Some(game) => format!(
+                "{} {}",
+                game.level
+                    .as_deref()
+                    .map(|level| level_name(project, level))
+                    .unwrap_or_default(),
+                format_play_time(game.play_time)
+            ),
+            None => "save.empty".to_string(),
+        };
+        values.set_text(id, text);
+    }
+}
+
+/// Continue loads the latest slot, choosing a slot starts a new game in it.
+fn handle_title_save_events(
+    mut commands: Commands,
+    mut activated_events: EventReader<MenuActivated>,
+    slots: Res<SaveSlots>,
+    mut active: ResMut<ActiveSave>,
+    mut level_selection: ResMut<LevelSelection>,
+    mut pending_placement: ResMut<PendingPlacement>,
+) {
+    for event in activated_events
+        .read()
+        .filter(|event| event.menu == "title")
+    {
+        let (slot, game) = match event.entry {
+            "continue" => {
+                let Some(slot) = slots.latest() else {
+                    continue;
+                };
+                (slot, slots.slots[slot].clone().unwrap_or_default())
+            }
+            entry => match SLOT_IDS.iter().position(|id| *id == entry) {
+                Some(slot) => (slot, SaveGame::default()),
+                None => continue,
+            },
+        };
+
+        if let Some(level) = &game.level {
+            *level_selection = LevelSelection::iid(level.clone());
+        }
+        pending_placement.0 = game.checkpoint;
+        *active = ActiveSave::start(Some(slot), game);
+        commands.insert_resource(NextState(Some(AppState::Playing)));
+    }
+}
+
+/// Runs before `HandleInput`, so the level selection already sees the loaded
+/// position.
+fn place_player(
+    mut pending_placement: ResMut<PendingPlacement>,
+    mut player_query: Query<(&PlayerId, &mut Transform, &mut PhysicsInterpolation), Added<Player>>,
+) {
+    let Some(translation) = pending_placement.0 else {
+        return;
+    };
+    for (player_id, mut transform, mut interpolation) in player_query.iter_mut() {
+        if *player_id == PlayerId(0) {
+            transform.translation = translation.truncate().extend(transform.translation.z);
+            interpolation.reset();
+            pending_placement.0 = None;
+        }
+    }
+}
+
+/// Also runs in practice games, level times are measured in play time.
+fn track_play_time(mut active: ResMut<ActiveSave>, time: Res<Time<Virtual>>) {
+    active.game.play_time += time.delta_seconds();
+}
+
+fn count_stats(
+    mut active: ResMut<ActiveSave>,
+    mut jumped_events: EventReader<PlayerJumped>,
+    mut input_events: EventReader<GameInputEvent>,
+) {
+    let jumps = jumped_events.read().count() as u32;
+    let restarts = input_events
+        .read()
+        .filter(|event| event.input == GameInput::ResetLevel)
+        .count() as u32;
+    if jumps > 0 || restarts > 0 {
+        active.game.stats.jumps += jumps;
+        active.game.stats.level_restarts += restarts;
+    }
+}
+
+fn collect_items(
+    mut active: ResMut<ActiveSave>,
+    mut pickup_collected_events: EventReader<PickupCollected>,
+) {
+    for event in pickup_collected_events.read() {
+        active.game.collected_items.insert(event.iid.clone());
+        if let Some(ability) = &event.ability {
+            active.game.abilities.insert(ability.clone());
+        }
+    }
+}
+
+fn remove_collected_pickups(
+    mut commands: Commands,
+    active: Res<ActiveSave>,
+    pickup_query: Query<(Entity, &EntityInstance), Added<Pickup>>,
+) {
+    for (entity, entity_instance) in pickup_query.iter() {
+        if active.game.collected_items.contains(&entity_instance.iid) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn count_death(mut active: ResMut<ActiveSave>) {
+    active.game.stats.deaths += 1;
+}
+
+/// Reaching the goal completes the level and saves the game.
+fn finish_level(
+    mut active: ResMut<ActiveSave>,
+    mut slots: ResMut<SaveSlots>,
+    mut level_finished_events: EventReader<LevelFinished>,
+) {
+    let Some(event) = level_finished_events.read().last() else {
+        return;
+    };
+    let time = active.game.play_time - active.level_entered_at;
+    active.game.complete_level(event.level.clone(), time);
+    active.write(&mut slots);
+}
+
+/// Entering a level is a checkpoint and saves the game, there are no
+/// checkpoints within a level. Leaving a level, other than by respawning,
+/// completes it.
+fn reach_checkpoint(
+    mut active: ResMut<ActiveSave>,
+    mut slots: ResMut<SaveSlots>,
+    mut level_changed_events: EventReader<LevelChanged>,
+    player_query: Query<(&PlayerId, &Transform), With<Player>>,
+    spawned_query: Query<&PlayerId, Added<Player>>,
+) {
+    let respawned = spawned_query
+        .iter()
+        .any(|player_id| *player_id == PlayerId(0));
+    let Some(event) = level_changed_events.read().last() else {
+        return;
+    };
+
+    let play_time = active.game.play_time;
+    if let Some(previous) = event.previous.clone().filter(|_| !respawned) {
+        let time = play_time - active.level_entered_at;
+        active.game.complete_level(previous, time);
+    }
+
+    active.level_entered_at = play_time;
+    active.game.level = Some(event.level.clone());
+    active.game.checkpoint = player_query
+        .iter()
+        .find(|(player_id, _)| **player_id == PlayerId(0))
+        .map(|(_, transform)| transform.translation);
+    active.write(&mut slots);
+}
+
+/// Saves and closes the game when returning to the title screen, before the
+/// title menu reads the slots.
+pub fn close_save(mut active: ResMut<ActiveSave>, mut slots: ResMut<SaveSlots>) {
+    active.write(&mut slots);
+    *active = ActiveSave::default();
+}
+
+fn flush_save(mut active: ResMut<ActiveSave>, mut slots: ResMut<SaveSlots>) {
+    active.write(&mut slots);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(completed: bool, best_time: Option<f32>) -> LevelRecord {
+        LevelRecord {
+            completed,
+            best_time,
+        }
+    }
+
+    #[test]
+    fn merging_records_keeps_the_slot_progress() {
+        let mut slot = SaveGame {
+            saved_at: 100,
+            level: Some("b".to_string()),
+            checkpoint: Some(Vec3::new(1.0, 2.0, 3.0)),
+            play_time: 50.0,
+            ..Default::default()
+        };
+        let practice = SaveGame {
+            saved_at: 200,
+            level: Some("a".to_string()),
+            play_time: 80.0,
+            levels: [("a".to_string(), record(true, Some(12.0)))].into(),
+            ..Default::default()
+        };
+        slot.merge_records(&practice);
+
+        assert_eq!(slot.saved_at, 100);
+        assert_eq!(slot.level.as_deref(), Some("b"));
+        assert_eq!(slot.checkpoint, Some(Vec3::new(1.0, 2.0, 3.0)));
+        assert_eq!(slot.play_time, 50.0);
+        assert_eq!(slot.levels["a"], record(true, Some(12.0)));
+    }
+
+    #[test]
+    fn merging_records_keeps_the_best_time() {
+        let mut slot = SaveGame {
+            levels: [
+                ("a".to_string(), record(true, Some(10.0))),
+                ("b".to_string(), record(true, Some(30.0))),
+                ("c".to_string(), record(true, None)),
+            ]
+            .into(),
+            ..Default::default()
+        };
+        let practice = SaveGame {
+            levels: [
+                ("a".to_string(), record(true, Some(20.0))),
+                ("b".to_string(), record(true, Some(25.0))),
+                ("c".to_string(), record(false, Some(40.0))),
+                ("d".to_string(), record(false, None)),
+            ]
+            .into(),
+            ..Default::default()
+        };
+        slot.merge_records(&practice);
+
+        assert_eq!(slot.levels["a"], record(true, Some(10.0)));
+        assert_eq!(slot.levels["b"], record(true, Some(25.0)));
+        assert_eq!(slot.levels["c"], record(true, Some(40.0)));
+        assert_eq!(slot.levels["d"], record(false, None));
+    }
+
+    #[test]
+    fn completing_a_level_keeps_the_best_time() {
+        let mut game = SaveGame::default();
+        game.complete_level("a".to_string(), 20.0);
+        game.complete_level("a".to_string(), 15.0);
+        game.complete_level("a".to_string(), 30.0);
+        assert_eq!(game.levels["a"], record(true, Some(15.0)));
+    }
+}
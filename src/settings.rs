@@ -0,0 +1,283 @@
+use bevy::prelude::*;
+use bevy::window::{PresentMode, PrimaryWindow, WindowMode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use thiserror::Error;
+
+use crate::camera::ShakeSettings;
+use crate::controls;
+use crate::menu_builder::{MenuPage, MenuSet, MenuValue, MenuValueChanged, MenuValues};
+use crate::state::ShutdownSet;
+
+pub const RESOLUTIONS: [(u32, u32); 4] = [(1200, 750), (1280, 720), (1600, 900), (1920, 1080)];
+
+/// Language codes and the names shown in the settings menu.
+pub const LANGUAGES: [(&str, &str); 2] = [("en", "English"), ("de", "Deutsch")];
+
+/// `load_error` is the error of loading the settings in `main`, logged once
+/// logging is set up.
+pub struct SettingsPlugin {
+    pub load_error: Option<SettingsError>,
+}
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(error) = &self.load_error {
+            warn!("Failed to load settings, using defaults: {}", error);
+        }
+        app.init_resource::<Settings>()
+            .add_systems(
+                Update,
+                (
+                    sync_settings_menu_values.before(MenuSet),
+                    (
+                        update_settings_from_menu,
+                        apply_settings.run_if(resource_changed::<Settings>()),
+                        save_settings.run_if(resource_changed::<Settings>()),
+                    )
+                        .chain()
+                        .after(MenuSet),
+                ),
+            )
+            .add_systems(Last, save_settings.in_set(ShutdownSet));
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WindowModeSetting {
+    #[default]
+    Windowed,
+    Borderless,
+    Fullscreen,
+}
+
+impl WindowModeSetting {
+    const ALL: [WindowModeSetting; 3] = [
+        WindowModeSetting::Windowed,
+        WindowModeSetting::Borderless,
+        WindowModeSetting::Fullscreen,
+    ];
+
+    pub fn window_mode(&self) -> WindowMode {
+        match self {
+            WindowModeSetting::Windowed => WindowMode::Windowed,
+            WindowModeSetting::Borderless => WindowMode::BorderlessFullscreen,
+            WindowModeSetting::Fullscreen => WindowMode::Fullscreen,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SettingsError {
+    #[error("no config directory on this platform")]
+    NoConfigDir,
+    #[error("could not access settings file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse settings file: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("could not serialize settings: {0}")]
+    Serialize(#[from] ron::Error),
+}
+
+/// User options stored in `<config dir>/platformer/settings.ron`. Missing fields
+/// fall back to their defaults, so older files keep working.
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    pub window_mode: WindowModeSetting,
+    pub resolution: (u32, u32),
+    pub vsync: bool,
+    /// Applied as the `GlobalVolume` of every sound started afterwards.
+    pub master_volume: f32,
+    /// Not used yet, the game has no music or sound effects. Stored so the
+    /// settings file and menu stay the same once audio is added.
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub screen_shake: bool,
+    pub language: String,
+    /// Shows the `speedrun::SpeedrunPlugin` overlay.
+    pub speedrun_timer: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            window_mode: WindowModeSetting::Windowed,
+            resolution: RESOLUTIONS[0],
+            vsync: true,
+            master_volume: 1.0,
+            music_volume: 0.8,
+            sfx_volume: 0.8,
+            screen_shake: true,
+            language: "de".to_string(),
+            speedrun_timer: false,
+        }
+    }
+}
+
+/// Path of a file in `<config dir>/platformer`.
+pub fn config_path(file_name: &str) -> Result<PathBuf, SettingsError> {
+    let dir = dirs::config_dir().ok_or(SettingsError::NoConfigDir)?;
+    Ok(dir.join("platformer").join(file_name))
+}
+
+/// Reads a RON config file, `None` if it does not exist yet.
+pub fn read_config<T: DeserializeOwned>(file_name: &str) -> Result<Option<T>, SettingsError> {
+    let path = config_path(file_name)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = std::fs::read_to_string(path)?;
+    Ok(Some(ron::from_str(&content)?))
+}
+
+pub fn write_config<T: Serialize>(file_name: &str, value: &T) -> Result<(), SettingsError> {
+    let path = config_path(file_name)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let content = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+impl Settings {
+    const FILE_NAME: &'static str = "settings.ron";
+
+    /// Reads the settings file, a missing file yields the defaults.
+    pub fn load() -> Result<Settings, SettingsError> {
+        Ok(read_config(Self::FILE_NAME)?.unwrap_or_default())
+    }
+
+    pub fn save(&self) -> Result<(), SettingsError> {
+        write_config(Self::FILE_NAME, self)
+    }
+
+    pub fn present_mode(&self) -> PresentMode {
+        if self.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        }
+    }
+
+    /// Primary window as configured, used when the app is built.
+    pub fn window(&self) -> Window {
+        Window {
+            title: "Platformer".into(),
+            resolution: (self.resolution.0 as f32, self.resolution.1 as f32).into(),
+            mode: self.window_mode.window_mode(),
+            present_mode: self.present_mode(),
+            ..default()
+        }
+    }
+}
+
+pub fn settings_page() -> MenuPage {
+    MenuPage::new("menu.settings")
+        .choice(
+            "window_mode",
+            "settings.window_mode",
+            [
+                "settings.window_mode.windowed",
+                "settings.window_mode.borderless",
+                "settings.window_mode.fullscreen",
+            ],
+        )
+        .choice(
+            "resolution",
+            "settings.resolution",
+            RESOLUTIONS.map(|(width, height)| format!("{}x{}", width, height)),
+        )
+        .toggle("vsync", "settings.vsync")
+        .slider("master_volume", "settings.master_volume", 0.0, 1.0, 0.1)
+        .slider("music_volume", "settings.music_volume", 0.0, 1.0, 0.1)
+        .slider("sfx_volume", "settings.sfx_volume", 0.0, 1.0, 0.1)
+        .toggle("screen_shake", "settings.screen_shake")
+        .toggle("speedrun_timer", "settings.speedrun_timer")
+        .choice(
+            "language",
+            "settings.language",
+            LANGUAGES.map(|(_, name)| name),
+        )
+        .submenu("controls", "menu.controls", controls::controls_page())
+        .back("menu.back")
+}
+
+fn sync_settings_menu_values(settings: Res<Settings>, mut values: ResMut<MenuValues>) {
+    if !settings.is_changed() {
+        return;
+    }
+    let window_mode = WindowModeSetting::ALL
+        .iter()
+        .position(|mode| *mode == settings.window_mode)
+        .unwrap_or(0);
+    let resolution = RESOLUTIONS
+        .iter()
+        .position(|resolution| *resolution == settings.resolution)
+        .unwrap_or(0);
+    let language = LANGUAGES
+        .iter()
+        .position(|(code, _)| *code == settings.language)
+        .unwrap_or(0);
+
+    values.set_index("window_mode", window_mode);
+    values.set_index("resolution", resolution);
+    values.set_bool("vsync", settings.vsync);
+    values.set_float("master_volume", settings.master_volume);
+    values.set_float("music_volume", settings.music_volume);
+    values.set_float("sfx_volume", settings.sfx_volume);
+    values.set_bool("screen_shake", settings.screen_shake);
+    values.set_bool("speedrun_timer", settings.speedrun_timer);
+    values.set_index("language", language);
+}
+
+fn update_settings_from_menu(
+    mut changed_events: EventReader<MenuValueChanged>,
+    mut settings: ResMut<Settings>,
+) {
+    for event in changed_events.read() {
+        match (event.entry, event.value) {
+            ("window_mode", MenuValue::Index(index)) => {
+                settings.window_mode = WindowModeSetting::ALL[index % WindowModeSetting::ALL.len()];
+            }
+            ("resolution", MenuValue::Index(index)) => {
+                settings.resolution = RESOLUTIONS[index % RESOLUTIONS.len()];
+            }
+            ("vsync", MenuValue::Bool(vsync)) => settings.vsync = vsync,
+            ("master_volume", MenuValue::Float(volume)) => settings.master_volume = volume,
+            ("music_volume", MenuValue::Float(volume)) => settings.music_volume = volume,
+            ("sfx_volume", MenuValue::Float(volume)) => settings.sfx_volume = volume,
+            ("screen_shake", MenuValue::Bool(shake)) => settings.screen_shake = shake,
+            ("speedrun_timer", MenuValue::Bool(timer)) => settings.speedrun_timer = timer,
+            ("language", MenuValue::Index(index)) => {
+                settings.language = LANGUAGES[index % LANGUAGES.len()].0.to_string();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn apply_settings(
+    settings: Res<Settings>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+    mut shake_settings: ResMut<ShakeSettings>,
+    mut global_volume: ResMut<GlobalVolume>,
+) {
+    for mut window in window_query.iter_mut() {
+        window.mode = settings.window_mode.window_mode();
+        window.present_mode = settings.present_mode();
+        window
+            .resolution
+            .set(settings.resolution.0 as f32, settings.resolution.1 as f32);
+    }
+    shake_settings.enabled = settings.screen_shake;
+    *global_volume = GlobalVolume::new(settings.master_volume);
+}
+
+fn save_settings(settings: Res<Settings>) {
+    if let Err(error) = settings.save() {
+        warn!("Failed to save settings: {}", error);
+    }
+}
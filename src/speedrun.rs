@@ -0,0 +1,277 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::asset_loader::FontAssets;
+use crate::input::{GameInput, GameInputEvent};
+use crate::player::{Player, PlayerId};
+use crate::settings::{read_config, write_config, Settings, SettingsError};
+use crate::state::{AppState, ScheduleSet, ShutdownSet};
+use crate::world::{level_name, LevelChanged, LevelFinished};
+
+const FONT_SIZE: f32 = 28.0;
+const AHEAD_COLOR: Color = Color::rgb(0.3, 0.9, 0.3);
+const BEHIND_COLOR: Color = Color::rgb(0.9, 0.3, 0.3);
+
+pub struct SpeedrunPlugin;
+
+impl Plugin for SpeedrunPlugin {
+    fn build(&self, app: &mut App) {
+        let personal_best = PersonalBest::load().unwrap_or_else(|error| {
+            warn!("Failed to load personal best splits: {}", error);
+            PersonalBest::default()
+        });
+        app.init_resource::<SpeedrunTimer>()
+            .insert_resource(personal_best)
+            .add_systems(OnEnter(AppState::Playing), spawn_overlay)
+            .add_systems(
+                Update,
+                (start_timer, tick_timer, record_splits, update_overlay)
+                    .chain()
+                    .after(ScheduleSet::HandleInput)
+                    .run_if(in_state(AppState::Playing)),
+            )
+            .add_systems(
+                OnEnter(AppState::TitleScreen),
+                (save_personal_best, reset_timer).chain(),
+            )
+            .add_systems(Last, save_personal_best.in_set(ShutdownSet));
+    }
+}
+
+/// Time at which a level was left or finished, counted from the start of the
+/// run.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Split {
+    pub level: String,
+    pub time: f32,
+}
+
+/// Game time of the current run. Uses virtual time, so the pause menu does not
+/// count. The run ends when a goal is reached or when returning to the title
+/// screen.
+#[derive(Resource, Debug, Default)]
+pub struct SpeedrunTimer {
+    pub running: bool,
+    /// A goal was reached, the timer stays stopped until the next game.
+    pub finished: bool,
+    pub elapsed: f32,
+    pub splits: Vec<Split>,
+}
+
+/// Splits of the best run, stored in `<config dir>/platformer/splits.ron`. A run
+/// is better if it got further, or equally far in less time.
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PersonalBest {
+    pub splits: Vec<Split>,
+}
+
+impl PersonalBest {
+    const FILE_NAME: &'static str = "splits.ron";
+
+    pub fn load() -> Result<PersonalBest, SettingsError> {
+        Ok(read_config(Self::FILE_NAME)?.unwrap_or_default())
+    }
+
+    pub fn save(&self) -> Result<(), SettingsError> {
+        write_config(Self::FILE_NAME, self)
+    }
+
+    fn is_beaten_by(&self, splits: &[Split]) -> bool {
+        match (splits.last(), self.splits.last()) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(run), Some(best)) => {
+                splits.len() > self.splits.len()
+                    || (splits.len() == self.splits.len() && run.time < best.time)
+            }
+        }
+    }
+
+    /// Difference to the personal best at the same split, `None` if the best run
+    /// took another route or did not get that far.
+    fn delta(&self, index: usize, split: &Split) -> Option<f32> {
+        self.splits
+            .get(index)
+            .filter(|best| best.level == split.level)
+            .map(|best| split.time - best.time)
+    }
+}
+
+#[derive(Component)]
+struct SpeedrunOverlay;
+
+fn spawn_overlay(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    overlay_query: Query<(), With<SpeedrunOverlay>>,
+) {
+    if !overlay_query.is_empty() {
+        return;
+    }
+    commands.spawn((
+        SpeedrunOverlay,
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: font_assets.menu_font.clone(),
+                font_size: FONT_SIZE,
+                color: Color::WHITE,
+            },
+        )
+        .with_text_alignment(TextAlignment::Right)
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            right: Val::Px(10.0),
+            ..default()
+        }),
+    ));
+}
+
+/// The run starts with the first movement input of a game.
+fn start_timer(mut timer: ResMut<SpeedrunTimer>, mut input_events: EventReader<GameInputEvent>) {
+    let moved = input_events.read().any(|event| {
+        matches!(
+            event.input,
+            GameInput::MoveLeft(_) | GameInput::MoveRight(_) | GameInput::Jump
+        )
+    });
+    if moved && !timer.running && !timer.finished {
+        timer.running = true;
+    }
+}
+
+fn tick_timer(mut timer: ResMut<SpeedrunTimer>, time: Res<Time<Virtual>>) {
+    if timer.running {
+        timer.elapsed += time.delta_seconds();
+    }
+}
+
+/// Level changes caused by respawning player 0 are not splits. Reaching a goal
+/// adds the last split and ends the run.
+fn record_splits(
+    mut timer: ResMut<SpeedrunTimer>,
+    mut level_changed_events: EventReader<LevelChanged>,
+    mut level_finished_events: EventReader<LevelFinished>,
+    spawned_query: Query<&PlayerId, Added<Player>>,
+) {
+    let respawned = spawned_query
+        .iter()
+        .any(|player_id| *player_id == PlayerId(0));
+    for event in level_changed_events.read() {
+        if respawned {
+            continue;
+        }
+        let Some(level) = event.previous.clone() else {
+            continue;
+        };
+        if timer.running {
+            let time = timer.elapsed;
+            timer.splits.push(Split { level, time });
+        }
+    }
+    for event in level_finished_events.read() {
+        if timer.running {
+            let time = timer.elapsed;
+            timer.splits.push(Split {
+                level: event.level.clone(),
+                time,
+            });
+            timer.running = false;
+            timer.finished = true;
+        }
+    }
+}
+
+pub fn format_time(seconds: f32) -> String {
+    let minutes = (seconds / 60.0).floor();
+    format!("{}:{:05.2}", minutes, seconds - minutes * 60.0)
+}
+
+/// Shows the total time and every split, with the difference to the personal
+/// best in green when ahead and red when behind. The splits are only rebuilt
+/// when they change, otherwise just the time is updated.
+fn update_overlay(
+    timer: Res<SpeedrunTimer>,
+    personal_best: Res<PersonalBest>,
+    settings: Res<Settings>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    mut overlay_query: Query<(&mut Text, &mut Visibility), With<SpeedrunOverlay>>,
+    mut shown_splits: Local<usize>,
+) {
+    let project = ldtk_projects
+        .get_single()
+        .ok()
+        .and_then(|handle| ldtk_project_assets.get(handle));
+    let rebuild =
+        personal_best.is_changed() || settings.is_changed() || *shown_splits != timer.splits.len();
+    *shown_splits = timer.splits.len();
+
+    for (mut text, mut visibility) in overlay_query.iter_mut() {
+        visibility.set_if_neq(if settings.speedrun_timer {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+        if !settings.speedrun_timer {
+            continue;
+        }
+        if !rebuild {
+            let time = format_time(timer.elapsed);
+            if text.sections[0].value != time {
+                text.sections[0].value = time;
+            }
+            continue;
+        }
+
+        let style = text.sections[0].style.clone();
+        let mut sections = vec![TextSection::new(format_time(timer.elapsed), style.clone())];
+        for (index, split) in timer.splits.iter().enumerate() {
+            sections.push(TextSection::new(
+                format!(
+                    "\n{}  {}",
+                    level_name(project, &split.level),
+                    format_time(split.time)
+                ),
+                style.clone(),
+            ));
+            if let Some(delta) = personal_best.delta(index, split) {
+                sections.push(TextSection::new(
+                    format!("  {:+.2}", delta),
+                    TextStyle {
+                        color: if delta <= 0.0 {
+                            AHEAD_COLOR
+                        } else {
+                            BEHIND_COLOR
+                        },
+                        ..style.clone()
+                    },
+                ));
+            }
+        }
+        text.sections = sections;
+    }
+}
+
+fn save_personal_best(timer: Res<SpeedrunTimer>, mut personal_best: ResMut<PersonalBest>) {
+    if !personal_best.is_beaten_by(&timer.splits) {
+        return;
+    }
+    personal_best.splits = timer.splits.clone();
+    if let Err(error) = personal_best.save() {
+        warn!("Failed to save personal best splits: {}", error);
+    }
+}
+
+fn reset_timer(
+    mut commands: Commands,
+    mut timer: ResMut<SpeedrunTimer>,
+    overlay_query: Query<Entity, With<SpeedrunOverlay>>,
+) {
+    *timer = SpeedrunTimer::default();
+    for entity in overlay_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
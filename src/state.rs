@@ -1,56 +1,200 @@
+use bevy::app::AppExit;
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 
+use crate::input::MenuInputEvent;
+
+/// Rate of the fixed simulation step in `FixedUpdate`.
+pub const SIMULATION_HZ: f64 = 60.0;
+
 #[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
 pub enum ScheduleSet {
     CheckMenu,
     PauseMenu,
     HandleInput,
+    MenuInput,
+    SampleInput,
     MainUpdate,
     VelocityCorrection,
     TransformUpdate,
     SyncRapier,
     StepRapier,
     WritebackRapier,
+    /// Observes the finished simulation step, e.g. to record it. Gated like
+    /// the simulation, so nothing runs while it is stopped.
+    RecordStep,
+    Interpolate,
     PostTransformUpdate,
 }
 
+/// Runs in `Last` during the frame an `AppExit` is sent, for anything that has
+/// to be written to disk before the app closes.
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct ShutdownSet;
+
+fn log_shutdown() {
+    info!("Shutting down");
+}
+
 #[derive(States, Default, Debug, Hash, PartialEq, Eq, Clone, Copy)]
 pub enum AppState {
     #[default]
-    Running,
-    Menu,
+    Loading,
+    TitleScreen,
+    Playing,
+    Paused,
+    GameOver,
+}
+
+/// True on every screen that is navigated with `MenuInputEvent`s.
+fn in_menu_screen(state: Res<State<AppState>>) -> bool {
+    matches!(
+        state.get(),
+        AppState::TitleScreen | AppState::Paused | AppState::GameOver
+    )
+}
+
+/// Freezes the simulation sets for a number of fixed steps without pausing the
+/// game, used for hit-stop effects.
+#[derive(Resource, Debug, Default)]
+pub struct HitStop {
+    remaining_frames: u32,
+    frozen: bool,
+}
+
+impl HitStop {
+    pub fn request(&mut self, frames: u32) {
+        self.remaining_frames = self.remaining_frames.max(frames);
+    }
+}
+
+fn tick_hit_stop(mut hit_stop: ResMut<HitStop>) {
+    hit_stop.frozen = hit_stop.remaining_frames > 0;
+    hit_stop.remaining_frames = hit_stop.remaining_frames.saturating_sub(1);
+}
+
+fn simulation_running(hit_stop: Res<HitStop>) -> bool {
+    !hit_stop.frozen
+}
+
+/// Speed of game time relative to real time, e.g. `0.25` for slow motion. Only
+/// takes effect while the game is not paused.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct TimeScale(pub f32);
+
+impl Default for TimeScale {
+    fn default() -> Self {
+        TimeScale(1.0)
+    }
+}
+
+fn apply_time_scale(time_scale: Res<TimeScale>, mut time: ResMut<Time<Virtual>>) {
+    if time_scale.is_changed() {
+        time.set_relative_speed(time_scale.0.max(0.0));
+    }
+}
+
+fn pause_virtual_time(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+fn unpause_virtual_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+/// Set while a single simulation step is run manually from the pause menu.
+#[derive(Resource, Debug, Default)]
+struct FrameStep(bool);
+
+fn frame_stepping(frame_step: Res<FrameStep>) -> bool {
+    frame_step.0
+}
+
+fn frame_step_requested(mut menu_events: EventReader<MenuInputEvent>) -> bool {
+    menu_events
+        .read()
+        .filter(|event| matches!(event, MenuInputEvent::StepFrame))
+        .count()
+        > 0
+}
+
+/// Advances the paused simulation by exactly one fixed step.
+fn step_single_frame(world: &mut World) {
+    let timestep = world.resource::<Time<Fixed>>().timestep();
+    world.resource_mut::<Time<Fixed>>().advance_by(timestep);
+    *world.resource_mut::<Time>() = world.resource::<Time<Fixed>>().as_generic();
+
+    world.resource_mut::<FrameStep>().0 = true;
+    world.run_schedule(FixedUpdate);
+    world.resource_mut::<FrameStep>().0 = false;
+
+    *world.resource_mut::<Time>() = world.resource::<Time<Virtual>>().as_generic();
 }
 
 pub struct SchedulePlugin;
 
 impl Plugin for SchedulePlugin {
     fn build(&self, app: &mut App) {
-        app.add_state::<AppState>().configure_sets(
-            Update,
-            (
-                (ScheduleSet::HandleInput,).run_if(in_state(AppState::Running)),
-                ScheduleSet::CheckMenu,
+        app.add_state::<AppState>()
+            .init_resource::<HitStop>()
+            .init_resource::<TimeScale>()
+            .init_resource::<FrameStep>()
+            .insert_resource(Time::<Fixed>::from_hz(SIMULATION_HZ))
+            .configure_sets(
+                Update,
                 (
+                    (ScheduleSet::HandleInput,).run_if(in_state(AppState::Playing)),
+                    ScheduleSet::CheckMenu,
+                    (ScheduleSet::Interpolate, ScheduleSet::PostTransformUpdate)
+                        .chain()
+                        .run_if(in_state(AppState::Playing)),
+                    (ScheduleSet::MenuInput,).run_if(in_menu_screen),
+                    (ScheduleSet::PauseMenu,).run_if(in_state(AppState::Paused)),
+                )
+                    .chain(),
+            )
+            .configure_sets(
+                FixedUpdate,
+                (
+                    ScheduleSet::SampleInput,
                     ScheduleSet::MainUpdate,
                     ScheduleSet::VelocityCorrection,
                     ScheduleSet::TransformUpdate,
                     ScheduleSet::SyncRapier,
                     ScheduleSet::StepRapier,
                     ScheduleSet::WritebackRapier,
-                    ScheduleSet::PostTransformUpdate,
+                    ScheduleSet::RecordStep,
                 )
                     .chain()
-                    .run_if(in_state(AppState::Running)),
-                (ScheduleSet::PauseMenu,).run_if(in_state(AppState::Menu)),
+                    .run_if(
+                        in_state(AppState::Playing)
+                            .or_else(frame_stepping)
+                            .and_then(simulation_running),
+                    ),
             )
-                .chain(),
-        ).add_systems(
-            Update, (
-                RapierPhysicsPlugin::<NoUserData>::get_systems(PhysicsSet::SyncBackend).in_set(ScheduleSet::SyncRapier),
-                RapierPhysicsPlugin::<NoUserData>::get_systems(PhysicsSet::StepSimulation).in_set(ScheduleSet::StepRapier),
-                RapierPhysicsPlugin::<NoUserData>::get_systems(PhysicsSet::Writeback).in_set(ScheduleSet::WritebackRapier),
+            .configure_sets(Last, ShutdownSet.run_if(on_event::<AppExit>()))
+            .add_systems(Last, log_shutdown.in_set(ShutdownSet))
+            .add_systems(OnEnter(AppState::Paused), pause_virtual_time)
+            .add_systems(OnExit(AppState::Paused), unpause_virtual_time)
+            .add_systems(
+                Update,
+                (
+                    apply_time_scale,
+                    step_single_frame
+                        .run_if(frame_step_requested)
+                        .in_set(ScheduleSet::PauseMenu),
+                ),
             )
-        );
+            .add_systems(
+                FixedUpdate,
+                (
+                    tick_hit_stop
+                        .before(ScheduleSet::SampleInput)
+                        .run_if(in_state(AppState::Playing).or_else(frame_stepping)),
+                    RapierPhysicsPlugin::<NoUserData>::get_systems(PhysicsSet::SyncBackend).in_set(ScheduleSet::SyncRapier),
+                    RapierPhysicsPlugin::<NoUserData>::get_systems(PhysicsSet::StepSimulation).in_set(ScheduleSet::StepRapier),
+                    RapierPhysicsPlugin::<NoUserData>::get_systems(PhysicsSet::Writeback).in_set(ScheduleSet::WritebackRapier),
+                ),
+            );
     }
 }
@@ -0,0 +1,121 @@
+use bevy::app::AppExit;
+use bevy::prelude::*;
+
+use crate::asset_loader::FontAssets;
+use crate::level_select::{self, LevelList};
+use crate::localization::{Localization, LocalizedText};
+use crate::menu_builder::{MenuActivated, MenuPage, MenuRoot, MenuSet};
+use crate::save::{self, SaveSlots};
+use crate::settings;
+use crate::state::AppState;
+
+pub struct TitlePlugin;
+
+impl Plugin for TitlePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnEnter(AppState::TitleScreen),
+            spawn_title_screen.after(save::close_save),
+        )
+        .add_systems(
+            Update,
+            (handle_title_events,)
+                .after(MenuSet)
+                .run_if(in_state(AppState::TitleScreen)),
+        )
+        .add_systems(OnExit(AppState::TitleScreen), despawn_title_screen);
+    }
+}
+
+#[derive(Component)]
+struct TitleScreen;
+
+/// Continue is disabled without a save, level select without levels. New games
+/// and continuing are handled by `save::SavePlugin`, starting a selected level
+/// by `level_select::LevelSelectPlugin`.
+fn title_menu_page(has_save: bool, levels: &LevelList) -> MenuPage {
+    let page = MenuPage::new("")
+        .submenu("new_game", "title.new_game", save::slot_page())
+        .button("continue", "title.continue");
+    let page = if has_save { page } else { page.disabled() };
+    let page = page.submenu(
+        "level_select",
+        "title.level_select",
+        level_select::level_select_page(levels),
+    );
+    let page = if levels.0.is_empty() {
+        page.disabled()
+    } else {
+        page
+    };
+    page.submenu("settings", "menu.settings", settings::settings_page())
+        .button("quit", "menu.quit")
+}
+
+fn spawn_title_screen(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    asset_server: Res<AssetServer>,
+    localization: Res<Localization>,
+    save_slots: Res<SaveSlots>,
+    levels: Res<LevelList>,
+) {
+    commands
+        .spawn((
+            TitleScreen,
+            MenuRoot::new(
+                "title",
+                title_menu_page(save_slots.latest().is_some(), &levels),
+            ),
+            ImageBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                image: asset_server.load("Background_2.png").into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                LocalizedText("game.title".to_string()),
+                TextBundle::from_section(
+                    localization.get("game.title"),
+                    TextStyle {
+                        font: font_assets.menu_font.clone(),
+                        font_size: 80.0,
+                        color: Color::RED,
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::all(Val::Px(30.0)),
+                    ..default()
+                }),
+            ));
+        });
+}
+
+fn handle_title_events(
+    mut activated_events: EventReader<MenuActivated>,
+    mut exit_events: EventWriter<AppExit>,
+) {
+    for event in activated_events
+        .read()
+        .filter(|event| event.menu == "title")
+    {
+        match event.entry {
+            "quit" => exit_events.send(AppExit),
+            _ => {}
+        }
+    }
+}
+
+fn despawn_title_screen(mut commands: Commands, query: Query<Entity, With<TitleScreen>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -3,6 +3,7 @@ use crate::player;
 use crate::player_assets;
 use crate::state;
 use bevy::prelude::*;
+use bevy_asset_loader::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
 use bevy_rapier2d::{
     control::{CharacterAutostep, CharacterLength, KinematicCharacterController},
@@ -10,12 +11,22 @@ use bevy_rapier2d::{
     geometry::{Collider, Friction},
 };
 use std::collections::{HashMap, HashSet};
+
+/// How far the player may fall below the lowest level before the run is over.
+const FALL_OUT_MARGIN: f32 = 200.0;
+/// Optional String field of the `Pickup` entity.
+const ABILITY_FIELD: &str = "Ability";
+
 pub struct WorldPlugin;
 
 impl Plugin for WorldPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins((LdtkPlugin,))
             .insert_resource(LevelSelection::Uid(0))
+            .init_resource::<CurrentLevel>()
+            .add_event::<LevelChanged>()
+            .add_event::<LevelFinished>()
+            .add_event::<PickupCollected>()
             .insert_resource(LdtkSettings {
                 level_spawn_behavior: LevelSpawnBehavior::UseWorldTranslation {
                     load_level_neighbors: true,
@@ -25,31 +36,100 @@ impl Plugin for WorldPlugin {
             })
             .register_ldtk_int_cell::<WallBundle>(1)
             .register_ldtk_entity::<PlayerBundle>("Player")
-            .add_systems(Startup, (
-                setup,
-            ))
-            .add_systems(Update, (
-                restart_level, 
-                update_level_selection, 
-            ).in_set(state::ScheduleSet::HandleInput)
-            )
+            .register_ldtk_entity::<GoalBundle>("Goal")
+            .register_ldtk_entity::<PickupBundle>("Pickup")
+            .add_systems(OnEnter(state::AppState::Playing), (setup,))
+            .add_systems(OnEnter(state::AppState::TitleScreen), (despawn_world,))
             .add_systems(
                 Update,
+                (update_level_selection,).in_set(state::ScheduleSet::HandleInput),
+            )
+            .add_systems(
+                FixedUpdate,
                 (
-                    spawn_wall_collision
-                ).in_set(state::ScheduleSet::MainUpdate),
+                    spawn_wall_collision,
+                    check_fall_out_of_world,
+                    check_goal_reached,
+                    collect_pickups,
+                    restart_level,
+                )
+                    .in_set(state::ScheduleSet::MainUpdate),
             );
     }
 }
 
-pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let ldtk_handle = asset_server.load("tile-based-game.ldtk");
+/// Loaded by `loading::LoadingPlugin` before the title screen is shown.
+#[derive(AssetCollection, Resource)]
+pub struct LevelAssets {
+    #[asset(path = "tile-based-game.ldtk")]
+    pub project: Handle<LdtkProject>,
+}
+
+/// Spawns the LDtk world when a game is started. Resuming from the pause menu
+/// keeps the existing world.
+pub fn setup(
+    mut commands: Commands,
+    level_assets: Res<LevelAssets>,
+    world_query: Query<(), With<Handle<LdtkProject>>>,
+) {
+    if !world_query.is_empty() {
+        return;
+    }
     commands.spawn(LdtkWorldBundle {
-        ldtk_handle,
+        ldtk_handle: level_assets.project.clone(),
         ..Default::default()
     });
 }
 
+/// Removes the world when returning to the title screen, so the next game starts
+/// from the first level again.
+pub fn despawn_world(
+    mut commands: Commands,
+    world_query: Query<Entity, With<Handle<LdtkProject>>>,
+    mut level_selection: ResMut<LevelSelection>,
+    mut current_level: ResMut<CurrentLevel>,
+) {
+    for entity in world_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    *level_selection = LevelSelection::Uid(0);
+    current_level.0 = None;
+}
+
+/// Identifier of a level for display, the iid if the level is unknown.
+pub fn level_name(project: Option<&LdtkProject>, iid: &str) -> String {
+    project
+        .and_then(|project| project.get_raw_level_by_iid(iid))
+        .map(|level| level.identifier.clone())
+        .unwrap_or_else(|| iid.to_string())
+}
+
+/// Iid of the level player 0 is in, `None` until the first level is entered.
+#[derive(Resource, Debug, Default)]
+pub struct CurrentLevel(pub Option<String>);
+
+/// Sent when player 0 enters another level, including the first level of a game.
+#[derive(Event, Debug, Clone)]
+pub struct LevelChanged {
+    pub previous: Option<String>,
+    pub level: String,
+}
+
+/// Sent at the simulation step player 0 reaches the goal of a level, once per
+/// attempt since the goal is respawned with the level.
+#[derive(Event, Debug, Clone)]
+pub struct LevelFinished {
+    pub level: String,
+}
+
+/// Sent when a player touches a pickup, the pickup is removed.
+#[derive(Event, Debug, Clone)]
+pub struct PickupCollected {
+    /// Iid of the pickup entity.
+    pub iid: String,
+    pub ability: Option<String>,
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
 pub struct Wall;
 
@@ -60,11 +140,14 @@ pub struct WallBundle {
 
 #[derive(Clone, Bundle, LdtkEntity)]
 pub struct PlayerBundle {
-    pub transform: TransformBundle,
+    pub spatial: SpatialBundle,
     pub player: player::Player,
+    pub player_id: player::PlayerId,
     #[from_entity_instance]
     entity_instance: EntityInstance,
     velocity: player::Velocity,
+    ground_state: player::GroundState,
+    interpolation: player::PhysicsInterpolation,
     controller: KinematicCharacterController,
     #[from_entity_instance]
     collider: ColliderBundle,
@@ -73,10 +156,13 @@ pub struct PlayerBundle {
 impl Default for PlayerBundle {
     fn default() -> Self {
         PlayerBundle {
-            transform: TransformBundle::default(),
+            spatial: SpatialBundle::default(),
             player: player::Player,
+            player_id: Default::default(),
             entity_instance: Default::default(),
             velocity: Default::default(),
+            ground_state: Default::default(),
+            interpolation: Default::default(),
             controller: KinematicCharacterController {
                 autostep: Some(CharacterAutostep {
                     max_height: CharacterLength::Absolute(0.01),
@@ -92,6 +178,94 @@ impl Default for PlayerBundle {
     }
 }
 
+impl PlayerBundle {
+    /// Player that joined with a gamepad, built from the entity of the player
+    /// placed in the level.
+    pub fn joined(
+        player_id: player::PlayerId,
+        entity_instance: &EntityInstance,
+        translation: Vec3,
+    ) -> Self {
+        PlayerBundle {
+            spatial: SpatialBundle::from_transform(Transform::from_translation(translation)),
+            player_id,
+            entity_instance: entity_instance.clone(),
+            collider: ColliderBundle::from(entity_instance),
+            ..Default::default()
+        }
+    }
+}
+
+/// End of a level, reached when player 0 is inside its bounds.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct Goal {
+    reached: bool,
+}
+
+#[derive(Clone, Default, Bundle, LdtkEntity)]
+pub struct GoalBundle {
+    goal: Goal,
+    #[from_entity_instance]
+    entity_instance: EntityInstance,
+    spatial: SpatialBundle,
+    #[from_entity_instance]
+    sprite: MarkerSpriteBundle,
+}
+
+/// Item collected once per save. The `Ability` field names an ability the
+/// pickup unlocks.
+#[derive(Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct Pickup {
+    pub ability: Option<String>,
+}
+
+impl From<&EntityInstance> for Pickup {
+    fn from(entity_instance: &EntityInstance) -> Pickup {
+        Pickup {
+            ability: entity_instance
+                .get_string_field(ABILITY_FIELD)
+                .ok()
+                .filter(|ability| !ability.is_empty())
+                .cloned(),
+        }
+    }
+}
+
+#[derive(Clone, Default, Bundle, LdtkEntity)]
+pub struct PickupBundle {
+    #[from_entity_instance]
+    pickup: Pickup,
+    #[from_entity_instance]
+    entity_instance: EntityInstance,
+    spatial: SpatialBundle,
+    #[from_entity_instance]
+    sprite: MarkerSpriteBundle,
+}
+
+/// Rectangle in the color the entity has in the LDtk editor, for entities
+/// without a tile.
+#[derive(Clone, Default, Bundle)]
+pub struct MarkerSpriteBundle {
+    sprite: Sprite,
+    texture: Handle<Image>,
+}
+
+impl From<&EntityInstance> for MarkerSpriteBundle {
+    fn from(entity_instance: &EntityInstance) -> MarkerSpriteBundle {
+        MarkerSpriteBundle {
+            sprite: Sprite {
+                color: entity_instance.smart_color,
+                custom_size: Some(Vec2::new(
+                    entity_instance.width as f32,
+                    entity_instance.height as f32,
+                )),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Clone, Default, Bundle, LdtkIntCell)]
 pub struct ColliderBundle {
     pub collider: Collider,
@@ -101,7 +275,7 @@ impl From<&EntityInstance> for ColliderBundle {
     fn from(entity_instance: &EntityInstance) -> ColliderBundle {
         match entity_instance.identifier.as_ref() {
             "Player" => ColliderBundle {
-                collider: Collider::cuboid(7.0, 18.0),
+                collider: Collider::cuboid(7.0, player::PLAYER_FEET_OFFSET),
                 ..Default::default()
             },
             _ => ColliderBundle::default(),
@@ -116,7 +290,6 @@ pub fn spawn_wall_collision(
     level_query: Query<(Entity, &LevelIid)>,
     ldtk_projects: Query<&Handle<LdtkProject>>,
     ldtk_project_assets: Res<Assets<LdtkProject>>,
-    mut state: ResMut<NextState<state::AppState>>,
 ) {
     #[derive(Clone, Eq, PartialEq, Debug, Default, Hash)]
     struct Plate {
@@ -240,13 +413,93 @@ pub fn spawn_wall_collision(
             }
         });
     }
-    state.set(state::AppState::Running);
 }
 
+/// Ends the run once a player has fallen below every spawned level.
+pub fn check_fall_out_of_world(
+    level_query: Query<&GlobalTransform, With<LevelIid>>,
+    player_query: Query<&GlobalTransform, With<player::Player>>,
+    mut state: ResMut<NextState<state::AppState>>,
+) {
+    let Some(lowest_level) = level_query
+        .iter()
+        .map(|transform| transform.translation().y)
+        .reduce(f32::min)
+    else {
+        return;
+    };
+
+    for player_transform in &player_query {
+        if player_transform.translation().y < lowest_level - FALL_OUT_MARGIN {
+            state.set(state::AppState::GameOver);
+        }
+    }
+}
+
+pub fn check_goal_reached(
+    mut goal_query: Query<(&mut Goal, &EntityInstance, &GlobalTransform)>,
+    player_query: Query<(&player::PlayerId, &GlobalTransform), With<player::Player>>,
+    current_level: Res<CurrentLevel>,
+    mut level_finished_events: EventWriter<LevelFinished>,
+) {
+    let (Some(level), Some((_, player_transform))) = (
+        current_level.0.as_ref(),
+        player_query
+            .iter()
+            .find(|(player_id, _)| **player_id == player::PlayerId(0)),
+    ) else {
+        return;
+    };
+    let player_position = player_transform.translation().truncate();
+
+    for (mut goal, entity_instance, goal_transform) in goal_query.iter_mut() {
+        let half_size =
+            Vec2::new(entity_instance.width as f32, entity_instance.height as f32) / 2.0;
+        let offset = (player_position - goal_transform.translation().truncate()).abs();
+        if !goal.reached && offset.x < half_size.x && offset.y < half_size.y {
+            goal.reached = true;
+            level_finished_events.send(LevelFinished {
+                level: level.clone(),
+            });
+        }
+    }
+}
+
+/// Pickups are touched by the collider of any player.
+pub fn collect_pickups(
+    mut commands: Commands,
+    pickup_query: Query<(Entity, &Pickup, &EntityInstance, &GlobalTransform)>,
+    player_query: Query<&GlobalTransform, With<player::Player>>,
+    mut pickup_collected_events: EventWriter<PickupCollected>,
+) {
+    let player_half_size = Vec2::new(7.0, player::PLAYER_FEET_OFFSET);
+    for (entity, pickup, entity_instance, pickup_transform) in pickup_query.iter() {
+        let reach = player_half_size
+            + Vec2::new(entity_instance.width as f32, entity_instance.height as f32) / 2.0;
+        let touched = player_query.iter().any(|player_transform| {
+            let offset = (player_transform.translation() - pickup_transform.translation())
+                .truncate()
+                .abs();
+            offset.x < reach.x && offset.y < reach.y
+        });
+        if touched {
+            pickup_collected_events.send(PickupCollected {
+                iid: entity_instance.iid.clone(),
+                ability: pickup.ability.clone(),
+            });
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Selects the level player 0 is in, so the camera and level loading follow the
+/// first player in co-op.
 pub fn update_level_selection(
     level_query: Query<(&LevelIid, &Transform), Without<player::Player>>,
-    player_query: Query<&Transform, With<player::Player>>,
+    player_query: Query<(&Transform, &player::PlayerId), With<player::Player>>,
     mut level_selection: ResMut<LevelSelection>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut level_changed_events: EventWriter<LevelChanged>,
     ldtk_projects: Query<&Handle<LdtkProject>>,
     ldtk_project_assets: Res<Assets<LdtkProject>>,
 ) {
@@ -267,27 +520,36 @@ pub fn update_level_selection(
             ),
         };
 
-        for player_transform in &player_query {
-            if player_transform.translation.x < level_bounds.max.x
+        for (player_transform, player_id) in &player_query {
+            if *player_id == player::PlayerId(0)
+                && player_transform.translation.x < level_bounds.max.x
                 && player_transform.translation.x > level_bounds.min.x
                 && player_transform.translation.y < level_bounds.max.y
                 && player_transform.translation.y > level_bounds.min.y
-                && !level_selection.is_match(&LevelIndices::default(), level)
             {
-                *level_selection = LevelSelection::iid(level.iid.clone());
+                if !level_selection.is_match(&LevelIndices::default(), level) {
+                    *level_selection = LevelSelection::iid(level.iid.clone());
+                }
+                if current_level.0.as_ref() != Some(&level.iid) {
+                    level_changed_events.send(LevelChanged {
+                        previous: current_level.0.replace(level.iid.clone()),
+                        level: level.iid.clone(),
+                    });
+                }
             }
         }
     }
 }
 
+/// Runs at a simulation step, resetting is recorded in replays like movement.
 pub fn restart_level(
     mut commands: Commands,
     level_query: Query<Entity, With<LevelIid>>,
     mut input: EventReader<input::GameInputEvent>,
 ) {
     for event in input.read() {
-        match event {
-            input::GameInputEvent::ResetLevel => {
+        match event.input {
+            input::GameInput::ResetLevel => {
                 for level_entity in &level_query {
                     commands.entity(level_entity).insert(Respawn);
                 }